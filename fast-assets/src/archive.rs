@@ -0,0 +1,245 @@
+use crate::error::{Error, Result};
+use std::io::{Cursor, Read, Seek, Write};
+use std::path::Path;
+
+/// The archive formats read without the extensions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    #[cfg(feature = "tar")]
+    Tar,
+    #[cfg(feature = "tar-gz")]
+    TarGz,
+    #[cfg(feature = "tar-zst")]
+    TarZst,
+}
+
+impl ArchiveFormat {
+    pub fn all() -> Vec<Self> {
+        vec![
+            ArchiveFormat::Zip,
+            #[cfg(feature = "tar")]
+            ArchiveFormat::Tar,
+            #[cfg(feature = "tar-gz")]
+            ArchiveFormat::TarGz,
+            #[cfg(feature = "tar-zst")]
+            ArchiveFormat::TarZst,
+        ]
+    }
+
+    /// Return the extensions of the format, without the first dot
+    pub fn extensions(&self) -> &'static [&'static str] {
+        match self {
+            ArchiveFormat::Zip => &["zip"],
+            #[cfg(feature = "tar")]
+            ArchiveFormat::Tar => &["tar"],
+            #[cfg(feature = "tar-gz")]
+            ArchiveFormat::TarGz => &["tar.gz", "tgz"],
+            #[cfg(feature = "tar-zst")]
+            ArchiveFormat::TarZst => &["tar.zst", "tzst"],
+        }
+    }
+
+    /// Detect the format using the file name, including the extensions with multiple dots like "tar.gz"
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        Self::all().into_iter().find(|format| {
+            format
+                .extensions()
+                .iter()
+                .any(|extension| name.ends_with(&format!(".{}", extension)))
+        })
+    }
+}
+
+/// Return the extensions of all the formats supported, used as default compression formats
+pub fn default_formats() -> Vec<String> {
+    ArchiveFormat::all()
+        .iter()
+        .flat_map(|format| format.extensions().iter().map(|ext| ext.to_string()))
+        .collect()
+}
+
+/// Return the names of the files in an archive,
+/// for zip archives only the central directory is read
+pub fn list_entries<R: Read + Seek>(format: ArchiveFormat, reader: R) -> Result<Vec<String>> {
+    match format {
+        ArchiveFormat::Zip => {
+            let archive = zip::ZipArchive::new(reader)?;
+            Ok(archive
+                .file_names()
+                .filter(|name| !name.ends_with('/'))
+                .map(|name| name.to_string())
+                .collect())
+        }
+        #[cfg(feature = "tar")]
+        _ => {
+            let mut archive = tar::Archive::new(tar_reader(format, reader)?);
+            let mut names = Vec::<String>::new();
+            for entry in archive.entries()? {
+                let entry = entry?;
+                if entry.header().entry_type().is_file() {
+                    names.push(tar_entry_name(&entry)?);
+                }
+            }
+            Ok(names)
+        }
+    }
+}
+
+/// Read the files of an archive, or only the selected ones.
+/// Return an error if a selected file is missing.
+pub fn read_entries(
+    format: ArchiveFormat,
+    data: &[u8],
+    selection: Option<&[&str]>,
+) -> Result<Vec<(String, Vec<u8>)>> {
+    let selected = |name: &str| match selection {
+        Some(selection) => selection.contains(&name),
+        None => true,
+    };
+
+    let mut entries = Vec::<(String, Vec<u8>)>::new();
+    match format {
+        ArchiveFormat::Zip => {
+            let mut archive = zip::ZipArchive::new(Cursor::new(data))?;
+            for i in 0..archive.len() {
+                let mut file = archive.by_index(i)?;
+                if !file.is_file() {
+                    continue;
+                }
+
+                let name = match file.enclosed_name() {
+                    Some(name) => name.to_string_lossy().to_string(),
+                    None => {
+                        return Err(Error::ArchiveCorrupt(format!(
+                            "Invalid entry name \"{}\"",
+                            file.name()
+                        )))
+                    }
+                };
+
+                if selected(&name) {
+                    let mut content = Vec::<u8>::new();
+                    file.read_to_end(&mut content)?;
+                    entries.push((name, content));
+                }
+            }
+        }
+        #[cfg(feature = "tar")]
+        _ => {
+            let mut archive = tar::Archive::new(tar_reader(format, Cursor::new(data))?);
+            for entry in archive.entries()? {
+                let mut entry = entry?;
+                if !entry.header().entry_type().is_file() {
+                    continue;
+                }
+
+                let name = tar_entry_name(&entry)?;
+                if selected(&name) {
+                    let mut content = Vec::<u8>::new();
+                    entry.read_to_end(&mut content)?;
+                    entries.push((name, content));
+                }
+            }
+        }
+    }
+
+    if let Some(selection) = selection {
+        for name in selection {
+            if !entries.iter().any(|(entry, _)| entry == name) {
+                return Err(Error::ArchiveCorrupt(format!("Missing entry \"{}\"", name)));
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Read a single file of an archive
+pub fn read_entry(format: ArchiveFormat, data: &[u8], entry: &str) -> Result<Vec<u8>> {
+    if format == ArchiveFormat::Zip {
+        // Zip archives give a direct access to the entry
+        let mut archive = zip::ZipArchive::new(Cursor::new(data))?;
+        let mut file = archive.by_name(entry)?;
+        let mut content = Vec::<u8>::new();
+        file.read_to_end(&mut content)?;
+        return Ok(content);
+    }
+
+    let mut entries = read_entries(format, data, Some(&[entry]))?;
+    Ok(entries.remove(0).1)
+}
+
+/// Rebuild a zip archive replacing the content of some entries,
+/// the other entries are copied without being decompressed.
+/// The replaced entries keep their compression method, and the new entries are added at the end.
+pub fn replace_zip_entries(data: &[u8], replacements: &[(&str, &[u8])]) -> Result<Vec<u8>> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(data))?;
+    let mut writer = zip::ZipWriter::new(Cursor::new(Vec::<u8>::new()));
+    writer.set_comment(String::from_utf8_lossy(archive.comment()).to_string());
+
+    let mut replaced = Vec::<&str>::new();
+    for i in 0..archive.len() {
+        let file = archive.by_index(i)?;
+        let replacement = replacements
+            .iter()
+            .find(|(name, _)| file.is_file() && *name == file.name());
+        match replacement {
+            Some((name, content)) => {
+                let mut options = zip::write::FileOptions::default()
+                    .compression_method(file.compression())
+                    .last_modified_time(file.last_modified())
+                    .large_file(content.len() as u64 >= u32::MAX as u64);
+                if let Some(mode) = file.unix_mode() {
+                    options = options.unix_permissions(mode);
+                }
+                writer.start_file(*name, options)?;
+                writer.write_all(content)?;
+                replaced.push(name);
+            }
+            None => writer.raw_copy_file(file)?,
+        }
+    }
+
+    for (name, content) in replacements {
+        if !replaced.contains(name) {
+            let options = zip::write::FileOptions::default()
+                .large_file(content.len() as u64 >= u32::MAX as u64);
+            writer.start_file(*name, options)?;
+            writer.write_all(content)?;
+        }
+    }
+
+    Ok(writer.finish()?.into_inner())
+}
+
+#[cfg(feature = "tar")]
+fn tar_reader<'a, R: Read + 'a>(format: ArchiveFormat, reader: R) -> Result<Box<dyn Read + 'a>> {
+    Ok(match format {
+        #[cfg(feature = "tar-gz")]
+        ArchiveFormat::TarGz => Box::new(flate2::read::GzDecoder::new(reader)),
+        #[cfg(feature = "tar-zst")]
+        ArchiveFormat::TarZst => Box::new(zstd::stream::read::Decoder::new(reader)?),
+        _ => Box::new(reader),
+    })
+}
+
+/// Return the name of a tar entry, without the "./" used by some tools
+#[cfg(feature = "tar")]
+fn tar_entry_name<R: Read>(entry: &tar::Entry<R>) -> Result<String> {
+    let path = entry.path()?;
+    if path.is_absolute()
+        || path
+            .components()
+            .any(|component| component == std::path::Component::ParentDir)
+    {
+        return Err(Error::ArchiveCorrupt(format!(
+            "Invalid entry name \"{}\"",
+            path.display()
+        )));
+    }
+    Ok(crate::backend::normalize(&path)
+        .to_string_lossy()
+        .replace('\\', "/"))
+}
@@ -0,0 +1,448 @@
+use crate::error::{Error, Result};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Debug;
+use std::io::{Cursor, Read, Seek};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::SystemTime;
+
+/// Information about a file or a directory of a backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Metadata {
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+    pub is_dir: bool,
+}
+
+/// A file opened for reading
+pub trait ReadSeek: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> ReadSeek for T {}
+
+/// Storage used by the Index and the AssetsManager to access the files
+pub trait Backend: Debug + Send + Sync {
+    /// Return the content of a file
+    fn read(&self, path: &Path) -> Result<Vec<u8>>;
+
+    /// Create or replace a file
+    fn write(&self, path: &Path, data: &[u8]) -> Result<()>;
+
+    /// Replace a file without exposing a partially written file, by default same as write
+    fn write_atomic(&self, path: &Path, data: &[u8]) -> Result<()> {
+        self.write(path, data)
+    }
+
+    /// Return the files and directories directly in a directory, joined to its path
+    fn list(&self, path: &Path) -> Result<Vec<PathBuf>>;
+
+    /// Return the information about a file or a directory
+    fn stat(&self, path: &Path) -> Result<Metadata>;
+
+    /// Remove a file
+    fn remove(&self, path: &Path) -> Result<()>;
+
+    /// Return true if the files cannot be written or removed
+    fn is_read_only(&self) -> bool {
+        false
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.stat(path).is_ok()
+    }
+
+    /// Open a file for reading, by default the whole file is read in memory
+    fn open(&self, path: &Path) -> Result<Box<dyn ReadSeek>> {
+        Ok(Box::new(Cursor::new(self.read(path)?)))
+    }
+}
+
+/// Remove the "./" components, so the same file always have the same key
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| *component != Component::CurDir)
+        .collect()
+}
+
+fn not_found(path: &Path) -> Error {
+    Error::Io(std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        format!("File \"{}\" not found", path.display()),
+    ))
+}
+
+/// The file system of the computer, optionally inside a root directory
+#[derive(Debug, Default, Clone)]
+pub struct FsBackend {
+    root: PathBuf,
+}
+
+impl FsBackend {
+    pub fn new(root: &str) -> Self {
+        Self {
+            root: PathBuf::from(root),
+        }
+    }
+
+    /// Return the path of the file on the disk, the paths leaving the root are rejected
+    fn real_path(&self, path: &Path) -> Result<PathBuf> {
+        if self.root.as_os_str().is_empty() {
+            return Ok(path.to_path_buf());
+        }
+
+        let inside_root = path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+        if !inside_root {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "Path \"{}\" is outside of \"{}\"",
+                    path.display(),
+                    self.root.display()
+                ),
+            )));
+        }
+        Ok(self.root.join(path))
+    }
+}
+
+impl Backend for FsBackend {
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        Ok(std::fs::read(self.real_path(path)?)?)
+    }
+
+    fn write(&self, path: &Path, data: &[u8]) -> Result<()> {
+        Ok(std::fs::write(self.real_path(path)?, data)?)
+    }
+
+    /// Write a temporary file next to the file, then rename it
+    fn write_atomic(&self, path: &Path, data: &[u8]) -> Result<()> {
+        let real_path = self.real_path(path)?;
+        let mut temp = real_path.clone().into_os_string();
+        temp.push(format!(".tmp-{}", std::process::id()));
+        let temp = PathBuf::from(temp);
+
+        let result = std::fs::write(&temp, data).and_then(|_| std::fs::rename(&temp, &real_path));
+        if result.is_err() {
+            let _ = std::fs::remove_file(&temp);
+        }
+        Ok(result?)
+    }
+
+    fn list(&self, path: &Path) -> Result<Vec<PathBuf>> {
+        let mut result = Vec::<PathBuf>::new();
+        for item in self.real_path(path)?.read_dir()? {
+            result.push(path.join(item?.file_name()));
+        }
+        Ok(result)
+    }
+
+    fn stat(&self, path: &Path) -> Result<Metadata> {
+        let metadata = std::fs::metadata(self.real_path(path)?)?;
+        Ok(Metadata {
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+            is_dir: metadata.is_dir(),
+        })
+    }
+
+    fn remove(&self, path: &Path) -> Result<()> {
+        Ok(std::fs::remove_file(self.real_path(path)?)?)
+    }
+
+    fn open(&self, path: &Path) -> Result<Box<dyn ReadSeek>> {
+        Ok(Box::new(std::fs::File::open(self.real_path(path)?)?))
+    }
+}
+
+/// Return the direct children of a directory, from a list of files,
+/// the directories are implicit
+fn list_children<'a>(
+    files: impl Iterator<Item = &'a PathBuf>,
+    path: &Path,
+) -> Result<Vec<PathBuf>> {
+    let directory = normalize(path);
+    let mut found = false;
+    let mut result = Vec::<PathBuf>::new();
+    // The archives can contain many entries, so the children already found are kept in a set
+    let mut seen = HashSet::<PathBuf>::new();
+    for file in files {
+        if let Ok(relative) = file.strip_prefix(&directory) {
+            if let Some(child) = relative.components().next() {
+                found = true;
+                let child = path.join(child);
+                if seen.insert(child.clone()) {
+                    result.push(child);
+                }
+            }
+        }
+    }
+
+    if !found && !directory.as_os_str().is_empty() {
+        return Err(not_found(path));
+    }
+    Ok(result)
+}
+
+/// Return true if a directory contains some of the files
+fn is_implicit_dir<'a>(mut files: impl Iterator<Item = &'a PathBuf>, path: &Path) -> bool {
+    let directory = normalize(path);
+    files.any(|file| file.starts_with(&directory) && *file != directory)
+}
+
+/// Files stored in memory, useful for tests and generated content
+#[derive(Debug, Default)]
+pub struct MemoryBackend {
+    files: RwLock<BTreeMap<PathBuf, (Vec<u8>, SystemTime)>>,
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Backend for MemoryBackend {
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        let files = self.files.read().unwrap();
+        match files.get(&normalize(path)) {
+            Some((data, _)) => Ok(data.clone()),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn write(&self, path: &Path, data: &[u8]) -> Result<()> {
+        let mut files = self.files.write().unwrap();
+        files.insert(normalize(path), (data.to_vec(), SystemTime::now()));
+        Ok(())
+    }
+
+    fn list(&self, path: &Path) -> Result<Vec<PathBuf>> {
+        let files = self.files.read().unwrap();
+        list_children(files.keys(), path)
+    }
+
+    fn stat(&self, path: &Path) -> Result<Metadata> {
+        let files = self.files.read().unwrap();
+        match files.get(&normalize(path)) {
+            Some((data, modified)) => Ok(Metadata {
+                len: data.len() as u64,
+                modified: Some(*modified),
+                is_dir: false,
+            }),
+            None if is_implicit_dir(files.keys(), path) || normalize(path) == Path::new("") => {
+                Ok(Metadata {
+                    len: 0,
+                    modified: None,
+                    is_dir: true,
+                })
+            }
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn remove(&self, path: &Path) -> Result<()> {
+        let mut files = self.files.write().unwrap();
+        match files.remove(&normalize(path)) {
+            Some(_) => Ok(()),
+            None => Err(not_found(path)),
+        }
+    }
+}
+
+/// The content of a zip archive, read only
+#[derive(Debug)]
+pub struct ZipBackend {
+    archive: Mutex<zip::ZipArchive<Cursor<Vec<u8>>>>,
+    entries: BTreeMap<PathBuf, u64>,
+}
+
+impl ZipBackend {
+    pub fn open(path: &str) -> Result<Self> {
+        Self::from_bytes(std::fs::read(path)?)
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self> {
+        let mut archive = zip::ZipArchive::new(Cursor::new(data))?;
+        let mut entries = BTreeMap::new();
+        for i in 0..archive.len() {
+            let file = archive.by_index(i)?;
+            if file.is_file() {
+                if let Some(name) = file.enclosed_name() {
+                    entries.insert(normalize(name), file.size());
+                }
+            }
+        }
+
+        Ok(Self {
+            archive: Mutex::new(archive),
+            entries,
+        })
+    }
+}
+
+impl Backend for ZipBackend {
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        let name = normalize(path);
+        if !self.entries.contains_key(&name) {
+            return Err(not_found(path));
+        }
+
+        let mut archive = self.archive.lock().unwrap();
+        let mut file = archive.by_name(&name.to_string_lossy().replace('\\', "/"))?;
+        let mut data = Vec::<u8>::new();
+        file.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    fn write(&self, path: &Path, _: &[u8]) -> Result<()> {
+        Err(Error::Unsupported(format!(
+            "Cannot write \"{}\" in a zip backend",
+            path.display()
+        )))
+    }
+
+    fn list(&self, path: &Path) -> Result<Vec<PathBuf>> {
+        list_children(self.entries.keys(), path)
+    }
+
+    fn stat(&self, path: &Path) -> Result<Metadata> {
+        let name = normalize(path);
+        match self.entries.get(&name) {
+            Some(len) => Ok(Metadata {
+                len: *len,
+                modified: None,
+                is_dir: false,
+            }),
+            None if is_implicit_dir(self.entries.keys(), path) || name == Path::new("") => {
+                Ok(Metadata {
+                    len: 0,
+                    modified: None,
+                    is_dir: true,
+                })
+            }
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn remove(&self, path: &Path) -> Result<()> {
+        Err(Error::Unsupported(format!(
+            "Cannot remove \"{}\" from a zip backend",
+            path.display()
+        )))
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+}
+
+/// Combine multiple backends, the first one containing a file is used to read it,
+/// and the first one accepting to write is used for the new files
+#[derive(Debug, Default)]
+pub struct CombinedBackend {
+    backends: Vec<Arc<dyn Backend>>,
+}
+
+impl CombinedBackend {
+    pub fn new(backends: Vec<Arc<dyn Backend>>) -> Self {
+        Self { backends }
+    }
+
+    pub fn push(&mut self, backend: Arc<dyn Backend>) {
+        self.backends.push(backend);
+    }
+
+    fn find(&self, path: &Path) -> Option<&Arc<dyn Backend>> {
+        self.backends.iter().find(|backend| backend.exists(path))
+    }
+
+    /// Write using the backend containing the file, or the first one accepting it
+    fn write_with(&self, path: &Path, write: impl Fn(&dyn Backend) -> Result<()>) -> Result<()> {
+        if let Some(backend) = self.find(path) {
+            return write(backend.as_ref());
+        }
+
+        let mut last_error = not_found(path);
+        for backend in self.backends.iter() {
+            match write(backend.as_ref()) {
+                Ok(_) => return Ok(()),
+                Err(err) => last_error = err,
+            }
+        }
+        Err(last_error)
+    }
+}
+
+impl Backend for CombinedBackend {
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        match self.find(path) {
+            Some(backend) => backend.read(path),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn open(&self, path: &Path) -> Result<Box<dyn ReadSeek>> {
+        match self.find(path) {
+            Some(backend) => backend.open(path),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn write(&self, path: &Path, data: &[u8]) -> Result<()> {
+        self.write_with(path, |backend| backend.write(path, data))
+    }
+
+    fn write_atomic(&self, path: &Path, data: &[u8]) -> Result<()> {
+        self.write_with(path, |backend| backend.write_atomic(path, data))
+    }
+
+    fn list(&self, path: &Path) -> Result<Vec<PathBuf>> {
+        let mut found = false;
+        let mut result = Vec::<PathBuf>::new();
+        let mut seen = HashSet::<PathBuf>::new();
+        for backend in self.backends.iter() {
+            if let Ok(files) = backend.list(path) {
+                found = true;
+                for file in files {
+                    if seen.insert(file.clone()) {
+                        result.push(file);
+                    }
+                }
+            }
+        }
+
+        if found {
+            Ok(result)
+        } else {
+            Err(not_found(path))
+        }
+    }
+
+    fn stat(&self, path: &Path) -> Result<Metadata> {
+        match self.find(path) {
+            Some(backend) => backend.stat(path),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn remove(&self, path: &Path) -> Result<()> {
+        let mut removed = false;
+        for backend in self.backends.iter() {
+            if backend.exists(path) {
+                backend.remove(path)?;
+                removed = true;
+            }
+        }
+
+        if removed {
+            Ok(())
+        } else {
+            Err(not_found(path))
+        }
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.backends.iter().all(|backend| backend.is_read_only())
+    }
+}
@@ -0,0 +1,147 @@
+use crate::codec::Codec;
+use crate::error::{Error, Result};
+use crate::handle::AssetHandle;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+/// Identify a loading started using AssetsManager::load_async
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LoadTicket(u64);
+
+/// Where the data of a file come from
+#[derive(Debug, Clone)]
+pub(crate) enum LoadSource {
+    Download(String),
+    Disk(PathBuf),
+    /// A file stored compressed, at the path with the extension of the codec
+    Compressed {
+        path: PathBuf,
+        codec: Codec,
+    },
+    Archive {
+        archive: String,
+        entry: String,
+    },
+}
+
+impl LoadSource {
+    /// Return the path of the file once loaded in the manager
+    pub fn file_path(&self) -> PathBuf {
+        match self {
+            LoadSource::Download(url) => PathBuf::from(url),
+            LoadSource::Disk(path) => path.clone(),
+            LoadSource::Compressed { path, .. } => path.clone(),
+            LoadSource::Archive { archive, entry } => PathBuf::from(archive).join(entry),
+        }
+    }
+}
+
+/// The data read by a worker
+pub(crate) struct LoadedData {
+    pub ticket: LoadTicket,
+    pub source: LoadSource,
+    pub data: Result<Vec<u8>>,
+}
+
+/// Run the IO of the files on a thread pool, and keep the results until they are polled
+#[derive(Debug)]
+pub(crate) struct BackgroundLoader {
+    pool: Option<rayon::ThreadPool>,
+    worker_count: usize,
+    sender: Sender<LoadedData>,
+    receiver: Receiver<LoadedData>,
+    next_ticket: u64,
+    pending: usize,
+    completed: HashMap<LoadTicket, Result<AssetHandle>>,
+}
+
+impl Default for BackgroundLoader {
+    fn default() -> Self {
+        let (sender, receiver) = channel();
+        Self {
+            pool: None,
+            worker_count: 0,
+            sender,
+            receiver,
+            next_ticket: 0,
+            pending: 0,
+            completed: HashMap::new(),
+        }
+    }
+}
+
+impl BackgroundLoader {
+    /// Set the number of workers, 0 let rayon choose it
+    pub fn set_worker_count(&mut self, worker_count: usize) {
+        self.worker_count = worker_count;
+        self.pool = None;
+    }
+
+    pub fn new_ticket(&mut self) -> LoadTicket {
+        let ticket = LoadTicket(self.next_ticket);
+        self.next_ticket += 1;
+        ticket
+    }
+
+    /// Run the job on the thread pool, the thread pool is created on the first call
+    pub fn spawn<F>(&mut self, ticket: LoadTicket, source: LoadSource, job: F) -> Result<()>
+    where
+        F: FnOnce() -> Result<Vec<u8>> + Send + 'static,
+    {
+        if self.pool.is_none() {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(self.worker_count)
+                .thread_name(|i| format!("fast-assets-worker-{}", i))
+                .build()
+                .map_err(|err| Error::Io(std::io::Error::other(err.to_string())))?;
+            self.pool = Some(pool);
+        }
+
+        let sender = self.sender.clone();
+        if let Some(pool) = &self.pool {
+            pool.spawn(move || {
+                let data = job();
+                // The receiver is only dropped with the manager, so the result is no longer needed
+                let _ = sender.send(LoadedData {
+                    ticket,
+                    source,
+                    data,
+                });
+            });
+            self.pending += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Return the next data read by the workers
+    pub fn try_recv(&mut self) -> Option<LoadedData> {
+        let loaded = self.receiver.try_recv().ok()?;
+        self.pending -= 1;
+        Some(loaded)
+    }
+
+    pub fn complete(&mut self, ticket: LoadTicket, result: Result<AssetHandle>) {
+        self.completed.insert(ticket, result);
+    }
+
+    pub fn is_completed(&self, ticket: LoadTicket) -> bool {
+        self.completed.contains_key(&ticket)
+    }
+
+    pub fn take(&mut self, ticket: LoadTicket) -> Option<Result<AssetHandle>> {
+        self.completed.remove(&ticket)
+    }
+
+    pub fn drain(&mut self) -> Vec<(LoadTicket, Result<AssetHandle>)> {
+        let mut completed: Vec<_> = self.completed.drain().collect();
+        completed.sort_by_key(|(ticket, _)| ticket.0);
+        completed
+    }
+
+    /// Number of loadings still running on the workers
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+}
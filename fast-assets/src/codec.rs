@@ -0,0 +1,86 @@
+use crate::error::Result;
+#[cfg(any(feature = "gz", feature = "lz4"))]
+use std::io::{Read, Write};
+
+/// Compression of a single file, like "level.bin.zst"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Codec {
+    #[cfg(feature = "gz")]
+    Gzip,
+    #[cfg(feature = "zst")]
+    Zstd,
+    #[cfg(feature = "lz4")]
+    Lz4,
+}
+
+impl Codec {
+    pub fn all() -> Vec<Self> {
+        vec![
+            #[cfg(feature = "gz")]
+            Codec::Gzip,
+            #[cfg(feature = "zst")]
+            Codec::Zstd,
+            #[cfg(feature = "lz4")]
+            Codec::Lz4,
+        ]
+    }
+
+    /// Return the extension added to the compressed files, without the dot
+    pub fn extension(&self) -> &'static str {
+        match *self {
+            #[cfg(feature = "gz")]
+            Codec::Gzip => "gz",
+            #[cfg(feature = "zst")]
+            Codec::Zstd => "zst",
+            #[cfg(feature = "lz4")]
+            Codec::Lz4 => "lz4",
+        }
+    }
+
+    #[cfg_attr(
+        not(any(feature = "gz", feature = "zst", feature = "lz4")),
+        allow(unused_variables)
+    )]
+    pub fn decompress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        match *self {
+            #[cfg(feature = "gz")]
+            Codec::Gzip => {
+                let mut output = Vec::<u8>::new();
+                flate2::read::GzDecoder::new(data).read_to_end(&mut output)?;
+                Ok(output)
+            }
+            #[cfg(feature = "zst")]
+            Codec::Zstd => Ok(zstd::stream::decode_all(data)?),
+            #[cfg(feature = "lz4")]
+            Codec::Lz4 => {
+                let mut output = Vec::<u8>::new();
+                lz4_flex::frame::FrameDecoder::new(data).read_to_end(&mut output)?;
+                Ok(output)
+            }
+        }
+    }
+
+    #[cfg_attr(
+        not(any(feature = "gz", feature = "zst", feature = "lz4")),
+        allow(unused_variables)
+    )]
+    pub fn compress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        match *self {
+            #[cfg(feature = "gz")]
+            Codec::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data)?;
+                Ok(encoder.finish()?)
+            }
+            #[cfg(feature = "zst")]
+            Codec::Zstd => Ok(zstd::stream::encode_all(data, 0)?),
+            #[cfg(feature = "lz4")]
+            Codec::Lz4 => {
+                let mut encoder = lz4_flex::frame::FrameEncoder::new(Vec::new());
+                encoder.write_all(data)?;
+                Ok(encoder.finish().map_err(std::io::Error::from)?)
+            }
+        }
+    }
+}
@@ -1,8 +1,13 @@
 use std::{
+    collections::HashMap,
     io::{Read, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
+    sync::Arc,
 };
 
+use crate::archive::{self, ArchiveFormat};
+use crate::backend::{Backend, FsBackend};
+use crate::error::{Error, Result};
 use crate::extension::Extension;
 
 #[derive(Debug, Default)]
@@ -14,31 +19,24 @@ pub struct CachedFile {
 
 impl Drop for CachedFile {
     fn drop(&mut self) {
-        match &self.cache {
-            Some(cache) => {
-                match std::fs::remove_file(cache) {
-                    _ => (),
-                };
-            }
-            None => (),
+        if let Some(cache) = &self.cache {
+            let _ = std::fs::remove_file(cache);
         }
     }
 }
 
 impl CachedFile {
     pub fn set_cache_if_missing(&mut self, cache: &str) {
-        match &self.cache {
-            Some(_) => (),
-            None => {
-                let mut path = PathBuf::from(cache);
-                while path.exists() {
-                    path.set_file_name(&format!(
-                        "_{}",
-                        path.file_name().unwrap().to_string_lossy().to_string()
-                    ));
-                }
-                self.cache = Some(path);
+        if self.cache.is_none() {
+            let mut path = PathBuf::from(cache);
+            while path.exists() {
+                let file_name = match path.file_name() {
+                    Some(name) => name.to_string_lossy().to_string(),
+                    None => String::new(),
+                };
+                path.set_file_name(format!("_{}", file_name));
             }
+            self.cache = Some(path);
         }
     }
 
@@ -46,39 +44,67 @@ impl CachedFile {
         self.data.is_some()
     }
 
-    pub fn cache(&mut self) {
+    pub fn cache(&mut self) -> Result<()> {
         match &self.cache {
             Some(path) => {
                 let mut file = std::fs::File::options()
                     .create(true)
                     .write(true)
                     .truncate(true)
-                    .open(path)
-                    .unwrap();
-                file.write_all(&self.data.clone().unwrap()).unwrap();
+                    .open(path)?;
+                if let Some(data) = &self.data {
+                    file.write_all(data)?;
+                }
                 self.data = None;
+                Ok(())
             }
-            None => panic!("Cache path undefined"),
+            None => Err(Error::CacheUnset(self.origin.clone())),
         }
     }
 
-    pub fn load_cache(&mut self) {
+    pub fn load_cache(&mut self) -> Result<()> {
         match &self.cache {
             Some(path) => {
-                let mut file = std::fs::File::options().read(true).open(path).unwrap();
+                let mut file = std::fs::File::options().read(true).open(path)?;
                 let mut buffer = Vec::<u8>::new();
-                file.read_to_end(&mut buffer).unwrap();
+                file.read_to_end(&mut buffer)?;
                 self.data = Some(buffer);
+                Ok(())
             }
-            None => panic!("Cache path undefined"),
+            None => Err(Error::CacheUnset(self.origin.clone())),
+        }
+    }
+
+    /// Reload the data from the cache if it was unloaded
+    fn reload_if_cached(&mut self) -> Result<()> {
+        if self.data.is_none() && self.cache.is_some() {
+            self.load_cache()?;
         }
+        Ok(())
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct DecompressionManager {
     cache_location: PathBuf,
-    files: Vec<CachedFile>,
+    files: HashMap<String, CachedFile>,
+    /// The data of the archives contained in other archives
+    archives: HashMap<String, Arc<Vec<u8>>>,
+    /// The number of bytes of the nested archives
+    archives_size: usize,
+    backend: Arc<dyn Backend>,
+}
+
+impl Default for DecompressionManager {
+    fn default() -> Self {
+        Self {
+            cache_location: default_cache_location(),
+            files: HashMap::new(),
+            archives: HashMap::new(),
+            archives_size: 0,
+            backend: Arc::new(FsBackend::default()),
+        }
+    }
 }
 
 impl DecompressionManager {
@@ -86,159 +112,284 @@ impl DecompressionManager {
         Self::default()
     }
 
+    /// Set the storage used to read the archives
+    pub fn set_backend(&mut self, backend: Arc<dyn Backend>) {
+        self.backend = backend;
+    }
+
     pub fn set_cache_location(&mut self, cache_location: &str) {
         self.cache_location = PathBuf::from(cache_location);
     }
 
-    pub fn cache(&mut self, filename: &str) {
-        let cache_location = self.cache_location.to_string_lossy().to_string();
+    /// Write the data of a file in the cache location and unload it, it is read again on the next access.
+    /// The cache files are named using the path of the file, without its directories
+    pub fn cache(&mut self, filename: &str) -> Result<()> {
+        let cache_location = self
+            .cache_location
+            .join(filename.replace(['/', '\\', ':'], "_"));
+        std::fs::create_dir_all(&self.cache_location)?;
+        if let Some(file) = self.get_mut(filename) {
+            file.set_cache_if_missing(&cache_location.to_string_lossy());
+            file.cache()?;
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, filename: &str) -> Option<&CachedFile> {
+        self.files.get(filename)
+    }
+
+    pub fn get_mut(&mut self, filename: &str) -> Option<&mut CachedFile> {
+        self.files.get_mut(filename)
+    }
+
+    /// Return a copy of the data and auto manage the cache
+    pub fn get_data(&mut self, filename: &str) -> Result<Option<Vec<u8>>> {
         match self.get_mut(filename) {
             Some(file) => {
-                file.set_cache_if_missing(&format!("{}/{}", cache_location, filename));
-                file.cache();
+                file.reload_if_cached()?;
+                Ok(file.data.clone())
             }
-            None => (),
+            None => Ok(None),
         }
     }
 
-    pub fn get(&self, filename: &str) -> Option<&CachedFile> {
-        for file in self.files.iter() {
-            if file.origin.to_string_lossy() == filename {
-                return Some(file);
+    /// Return a ref of the data and auto manage the cache
+    pub fn get_data_ref(&mut self, filename: &str) -> Result<Option<&Option<Vec<u8>>>> {
+        match self.get_mut(filename) {
+            Some(file) => {
+                file.reload_if_cached()?;
+                Ok(Some(&file.data))
             }
+            None => Ok(None),
         }
-
-        None
     }
 
-    pub fn get_mut(&mut self, filename: &str) -> Option<&mut CachedFile> {
-        for file in self.files.iter_mut() {
-            if file.origin.to_string_lossy() == filename {
-                return Some(file);
+    /// Return a &mut of the data and auto manage the cache
+    pub fn get_data_mut(&mut self, filename: &str) -> Result<Option<&mut Option<Vec<u8>>>> {
+        match self.get_mut(filename) {
+            Some(file) => {
+                file.reload_if_cached()?;
+                Ok(Some(&mut file.data))
             }
+            None => Ok(None),
         }
+    }
 
-        None
+    /// Return the name of the decompressed file of an archive entry, like "packs/base.zip/textures/a.png",
+    /// so the entries with the same path in different archives are kept apart
+    pub fn entry_path(archive: &str, entry: &str) -> String {
+        Path::new(archive).join(entry).to_string_lossy().to_string()
     }
 
-    /// Return a copy of the data and auto manage the cache
-    pub fn get_data(&mut self, filename: &str) -> Option<Vec<u8>> {
-        for file in self.files.iter_mut() {
-            if file.origin.to_string_lossy() == filename {
-                if file.data.is_none() {
-                    if file.cache.is_some() {
-                        file.load_cache();
-                    }
-                }
-                return file.data.clone();
+    /// Give another name to a decompressed file, if there is no file with the new name
+    pub fn rename(&mut self, filename: &str, new_name: &str) {
+        if filename == new_name || self.files.contains_key(new_name) {
+            return;
+        }
+        if let Some(mut file) = self.files.remove(filename) {
+            file.origin = PathBuf::from(new_name);
+            self.files.insert(new_name.to_string(), file);
+        }
+    }
+
+    /// Add a decompressed file, replacing the previous one with the same name
+    pub fn add_file(&mut self, filename: &str, data: Vec<u8>) {
+        self.files.insert(
+            filename.to_string(),
+            CachedFile {
+                cache: None,
+                origin: PathBuf::from(filename),
+                data: Some(data),
+            },
+        );
+    }
+
+    /// Read a single entry of an archive, without adding it to the manager
+    pub fn read_entry(backend: &dyn Backend, archive: &str, entry: &str) -> Result<Vec<u8>> {
+        let data = Self::read_archive(backend, archive)?;
+        read_archive_entry(archive, &data, entry)
+    }
+
+    /// Return the data of an archive, the archives contained in other archives
+    /// (like "packs/base.zip/textures.zip") are read from their parent
+    pub fn read_archive(backend: &dyn Backend, archive: &str) -> Result<Vec<u8>> {
+        match split_nested_archive(backend, archive) {
+            Some((parent, entry)) => {
+                let data = Self::read_archive(backend, &parent)?;
+                read_archive_entry(&parent, &data, &entry)
             }
+            None => backend.read(Path::new(archive)),
         }
-        None
     }
 
-    /// Return a ref of the data and auto manage the cache
-    pub fn get_data_ref(&mut self, filename: &str) -> Option<&Option<Vec<u8>>> {
-        for file in self.files.iter_mut() {
-            if file.origin.to_string_lossy() == filename {
-                if file.data.is_none() {
-                    if file.cache.is_some() {
-                        file.load_cache();
-                    }
-                }
-                return Some(&file.data);
+    /// Same as read_archive, but keep the data of the nested archives
+    fn archive_data(&mut self, archive: &str) -> Result<Arc<Vec<u8>>> {
+        if let Some(data) = self.archives.get(archive) {
+            return Ok(data.clone());
+        }
+
+        match split_nested_archive(self.backend.as_ref(), archive) {
+            Some((parent, entry)) => {
+                let parent_data = self.archive_data(&parent)?;
+                let data = Arc::new(read_archive_entry(&parent, &parent_data, &entry)?);
+                self.insert_archive(archive, data.clone());
+                Ok(data)
             }
+            None => Ok(Arc::new(self.backend.read(Path::new(archive))?)),
         }
-        None
     }
 
-    /// Return a &mut of the data and auto manage the cache
-    pub fn get_data_mut(&mut self, filename: &str) -> Option<&mut Option<Vec<u8>>> {
-        for file in self.files.iter_mut() {
-            if file.origin.to_string_lossy() == filename {
-                if file.data.is_none() {
-                    if file.cache.is_some() {
-                        file.load_cache();
-                    }
-                }
-                return Some(&mut file.data);
+    /// Write the data of a decompressed file back into its zip archive,
+    /// the archive is rebuilt and replaced atomically
+    pub fn save_entry(&mut self, archive: &str, entry: &str) -> Result<()> {
+        let path = Self::entry_path(archive, entry);
+        let content = match self.get_data(&path)? {
+            Some(content) => content,
+            None => return Err(Error::NotIndexed(path)),
+        };
+        self.replace_entry(archive, entry, &content)
+    }
+
+    /// Replace an entry in an archive, and the archive in its parent if it is nested
+    fn replace_entry(&mut self, archive: &str, entry: &str, content: &[u8]) -> Result<()> {
+        if ArchiveFormat::from_path(Path::new(archive)) != Some(ArchiveFormat::Zip) {
+            return Err(Error::Unsupported(format!(
+                "Cannot write in \"{}\", only zip archives can be modified",
+                archive
+            )));
+        }
+
+        let data = self.archive_data(archive)?;
+        let data = archive::replace_zip_entries(&data, &[(entry, content)])?;
+        match split_nested_archive(self.backend.as_ref(), archive) {
+            Some((parent, parent_entry)) => {
+                self.replace_entry(&parent, &parent_entry, &data)?;
+                self.insert_archive(archive, Arc::new(data));
             }
+            None => self.backend.write_atomic(Path::new(archive), &data)?,
         }
-        None
+        Ok(())
+    }
+
+    fn insert_archive(&mut self, archive: &str, data: Arc<Vec<u8>>) {
+        self.archives_size += data.len();
+        if let Some(previous) = self.archives.insert(archive.to_string(), data) {
+            self.archives_size -= previous.len();
+        }
+    }
+
+    /// Remove the data of the nested archives, they are read again on the next use
+    pub fn clear_archive_cache(&mut self) {
+        self.archives.clear();
+        self.archives_size = 0;
+    }
+
+    /// Return the number of bytes of the nested archives loaded in memory
+    pub fn archive_memory_usage(&self) -> usize {
+        self.archives_size
+    }
+
+    /// Return the number of bytes of the decompressed files and nested archives loaded in memory
+    pub fn memory_usage(&self) -> usize {
+        let files: usize = self
+            .files
+            .values()
+            .map(|file| file.data.as_ref().map_or(0, |data| data.len()))
+            .sum();
+        files + self.archives_size
     }
 
     pub fn unload(&mut self, filename: &str) {
-        for file in self.files.iter_mut() {
-            if file.origin.to_string_lossy() == filename {
-                file.data = None;
-            }
+        if let Some(file) = self.files.get_mut(filename) {
+            file.data = None;
         }
     }
 
     pub fn remove(&mut self, filename: &str) {
-        for i in 0..self.files.len() {
-            if self.files[i].origin.to_string_lossy() == filename {
-                self.files.remove(i);
-            }
-        }
+        self.files.remove(filename);
     }
 
+    /// Add the files of an archive, or only the selected ones.
+    /// The formats not supported natively are given to the extensions.
     pub fn load_archive(
         &mut self,
         archive: &str,
         selection: Option<Vec<&str>>,
         process_pass_list: &mut Vec<Box<dyn Extension>>,
-    ) {
+    ) -> Result<()> {
         let path = PathBuf::from(archive);
 
-        match path
-            .extension()
-            .unwrap()
-            .to_string_lossy()
-            .to_string()
-            .as_str()
-        {
-            "zip" => {
-                let archive = std::fs::File::open(&path).unwrap();
-                let mut archive = zip::ZipArchive::new(archive).unwrap();
-                for i in 0..archive.len() {
-                    let mut file = archive.by_index(i).unwrap();
-                    if file.is_file() {
-                        match selection {
-                            Some(ref selection) => {
-                                for selected in selection.iter() {
-                                    let file_name =
-                                        file.enclosed_name().unwrap().to_string_lossy().to_string();
-                                    if file_name == *selected {
-                                        let mut cached_file = CachedFile::default();
-                                        cached_file.origin = PathBuf::from(file_name);
-                                        let mut data = Vec::<u8>::new();
-                                        file.read_to_end(&mut data).unwrap();
-                                        cached_file.data = Some(data);
-                                        self.files.push(cached_file);
-                                    }
-                                }
-                            }
-                            None => {
-                                let file_name =
-                                    file.enclosed_name().unwrap().to_string_lossy().to_string();
-                                let mut cached_file = CachedFile::default();
-                                cached_file.origin = PathBuf::from(file_name);
-                                let mut data = Vec::<u8>::new();
-                                file.read_to_end(&mut data).unwrap();
-                                cached_file.data = Some(data);
-                                self.files.push(cached_file);
-                            }
-                        }
+        if let Some(format) = ArchiveFormat::from_path(&path) {
+            let data = self.archive_data(archive)?;
+            let entries = archive::read_entries(format, &data, selection.as_deref()).map_err(
+                |err| match err {
+                    Error::ArchiveCorrupt(reason) => {
+                        Error::ArchiveCorrupt(format!("{} in \"{}\"", reason, archive))
                     }
-                }
+                    err => err,
+                },
+            )?;
+            for (name, data) in entries {
+                self.add_file(&Self::entry_path(archive, &name), data);
             }
-            extension => {
-                for i in 0..process_pass_list.len() {
-                    let mut process_pass = process_pass_list.swap_remove(i);
-                    process_pass.on_archive(self, extension, &path);
-                    process_pass_list.insert(i, process_pass);
-                }
+            return Ok(());
+        }
+
+        let extension = match path.extension() {
+            Some(extension) => extension.to_string_lossy().to_string(),
+            None => {
+                return Err(Error::Unsupported(format!(
+                    "\"{}\" has no archive extension",
+                    archive
+                )))
             }
+        };
+
+        for i in 0..process_pass_list.len() {
+            let mut process_pass = process_pass_list.swap_remove(i);
+            process_pass.on_archive(self, &extension, &path);
+            process_pass_list.insert(i, process_pass);
         }
+
+        Ok(())
+    }
+}
+
+/// A directory of the temporary directory, specific to the process
+fn default_cache_location() -> PathBuf {
+    std::env::temp_dir().join(format!("fast-assets-{}", std::process::id()))
+}
+
+/// Read an entry of an archive, using the format of the archive path
+fn read_archive_entry(archive_path: &str, archive: &[u8], entry: &str) -> Result<Vec<u8>> {
+    match ArchiveFormat::from_path(Path::new(archive_path)) {
+        Some(format) => archive::read_entry(format, archive, entry),
+        None => Err(Error::Unsupported(format!(
+            "\"{}\" is not a supported archive",
+            archive_path
+        ))),
     }
 }
+
+/// Split the path of an archive contained in another archive into the parent archive and the entry,
+/// return None if the archive is directly in the backend
+fn split_nested_archive(backend: &dyn Backend, archive: &str) -> Option<(String, String)> {
+    if backend.exists(Path::new(archive)) {
+        return None;
+    }
+
+    let components: Vec<String> = Path::new(archive)
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().to_string())
+        .collect();
+    let parent = components
+        .iter()
+        .take(components.len().saturating_sub(1))
+        .rposition(|component| ArchiveFormat::from_path(Path::new(component)).is_some())?;
+
+    Some((
+        components[..=parent].join("/"),
+        components[parent + 1..].join("/"),
+    ))
+}
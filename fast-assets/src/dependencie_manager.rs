@@ -1,21 +1,289 @@
+use crate::archive::ArchiveFormat;
+use crate::error::{Error, Result};
+use crate::index::Index;
 use crate::manager::AssetsManager;
+use crate::scanner::{CsvScanner, DependencyScanner, JsonScanner};
+use crate::version::{Version, VersionReq};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatternKind {
+    /// A file name or a path, like "grass.png"
+    File,
+    /// A glob matching the end of the indexed paths, like "textures/level1/*.png".
+    /// "*" and "?" do not match "/", and "**" match any number of directories.
+    Glob,
+    /// A regex matching the indexed paths, written "regex:<regex>" in the JSON file
+    Regex,
+}
+
+/// A dependency declared by a pattern, or an optional file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DependencyPattern {
+    pub pattern: String,
+    pub kind: PatternKind,
+    /// If false, at least one indexed file must match
+    pub optional: bool,
+    /// The version required for each file matching
+    pub version: Option<VersionReq>,
+}
+
+impl DependencyPattern {
+    /// Detect the kind of a pattern: "regex:" prefix, a glob if it contains "*" or "?", else a file.
+    /// Return Error::Regex if the regex is invalid.
+    pub fn parse(pattern: &str, optional: bool) -> Result<Self> {
+        let (pattern, kind) = match pattern.strip_prefix("regex:") {
+            Some(regex) => (regex, PatternKind::Regex),
+            None if pattern.contains(['*', '?']) => (pattern, PatternKind::Glob),
+            None => (pattern, PatternKind::File),
+        };
+        let pattern = Self {
+            pattern: pattern.to_string(),
+            kind,
+            optional,
+            version: None,
+        };
+        if let Some(regex) = pattern.regex() {
+            regex::Regex::new(&regex)?;
+        }
+        Ok(pattern)
+    }
+
+    pub fn with_version(mut self, version: VersionReq) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    fn regex(&self) -> Option<String> {
+        match self.kind {
+            PatternKind::File => None,
+            PatternKind::Glob => Some(glob_to_regex(&self.pattern)),
+            PatternKind::Regex => Some(self.pattern.clone()),
+        }
+    }
+
+    /// Return the indexed files matching the pattern
+    fn expand(&self, assets_manager: &AssetsManager) -> Vec<String> {
+        match self.regex() {
+            Some(regex) => assets_manager
+                .get_files_matching_regex(&regex)
+                .unwrap_or_default()
+                .iter()
+                .map(|file| file.to_string_lossy().to_string())
+                .collect(),
+            None if assets_manager.have_file(&self.pattern) => vec![self.pattern.clone()],
+            None => Vec::new(),
+        }
+    }
+}
+
+struct GraphNode {
+    name: String,
+    size: Option<u64>,
+    missing: bool,
+}
+
+/// Return the size of a file, saved in the index file or read from the backend of the index
+fn file_size(index: &Index, file: &str) -> Option<u64> {
+    let path = index.get_path(file).ok()?;
+    match index.file_info(&path) {
+        Some(info) => Some(info.len),
+        None => index
+            .backend()
+            .stat(Path::new(&path))
+            .ok()
+            .map(|metadata| metadata.len),
+    }
+}
+
+fn escape_dot(name: &str) -> String {
+    name.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Return the version of a file, or of the archives containing it from the innermost.
+/// The versions are declared using the indexed path, or a file name resolved by the index,
+/// and a file name used by several files is returned as an Error::Ambiguous
+fn find_version(
+    versions: &HashMap<String, Version>,
+    assets_manager: &AssetsManager,
+    file: &str,
+) -> Result<Option<Version>> {
+    let path = match assets_manager.index.get_path(file) {
+        Ok(path) => path,
+        Err(Error::NotIndexed(_)) => file.to_string(),
+        Err(err) => return Err(err),
+    };
+
+    let candidates = Path::new(&path)
+        .ancestors()
+        .enumerate()
+        .filter(|(depth, ancestor)| *depth == 0 || ArchiveFormat::from_path(ancestor).is_some())
+        .map(|(_, ancestor)| ancestor.to_string_lossy().to_string());
+    for candidate in candidates {
+        if let Some(version) = versions.get(&candidate) {
+            return Ok(Some(*version));
+        }
+        let name = match Path::new(&candidate).file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => continue,
+        };
+        let version = match versions.get(&name) {
+            Some(version) => *version,
+            None => continue,
+        };
+        match assets_manager.index.get_path(&name) {
+            Ok(resolved) if resolved == candidate => return Ok(Some(version)),
+            Err(Error::Ambiguous(files))
+                if files.iter().any(|file| *file == Path::new(&candidate)) =>
+            {
+                return Err(Error::Ambiguous(files))
+            }
+            _ => {}
+        }
+    }
+    Ok(None)
+}
+
+/// Return true if the file is indexed, using its file name, its full path or the end of its path
+fn is_indexed(assets_manager: &AssetsManager, file: &str) -> bool {
+    if assets_manager.have_file(file) {
+        return true;
+    }
+
+    // Only the files with the same name can end with the path
+    match Path::new(file).file_name() {
+        Some(name) if file.contains('/') => assets_manager
+            .index
+            .files_named(&name.to_string_lossy())
+            .iter()
+            .any(|indexed| same_file(&indexed.to_string_lossy(), file)),
+        _ => false,
+    }
+}
+
+/// Return true if the paths are the same, or one is the end of the other
+fn same_file(a: &str, b: &str) -> bool {
+    a == b || a.ends_with(&format!("/{}", b)) || b.ends_with(&format!("/{}", a))
+}
+
+/// Convert a glob to a regex matching the end of a path, after a "/"
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::from("(^|/)");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                // "**/" also match no directory
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    regex
+}
 
 #[derive(Debug, Default)]
 pub struct Dependencie {
     pub source: String,
+    /// The files required, each one must be indexed
     pub deps: Vec<String>,
+    /// The patterns and the optional files, expanded by update
+    pub patterns: Vec<DependencyPattern>,
+    /// The indexed files matching the patterns, found by update
+    pub matched: Vec<String>,
+    /// The required files and patterns not found by update
     pub missing_list: Vec<String>,
+    /// The files found by update without the version required
+    pub version_mismatches: Vec<VersionMismatch>,
 }
 
 impl Dependencie {
     pub fn is_valid(&self) -> bool {
-        self.missing_list.is_empty()
+        self.missing_list.is_empty() && self.version_mismatches.is_empty()
+    }
+
+    /// Return the required files and the files matching the patterns
+    pub fn files(&self) -> impl Iterator<Item = &String> {
+        self.deps.iter().chain(self.matched.iter())
+    }
+
+    fn add(&mut self, pattern: DependencyPattern) {
+        if pattern.kind == PatternKind::File && !pattern.optional && pattern.version.is_none() {
+            self.deps.push(pattern.pattern);
+        } else {
+            self.patterns.push(pattern);
+        }
+    }
+}
+
+/// A file whose version does not match the requirement of a dependency
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionMismatch {
+    /// The file declaring the dependency
+    pub source: String,
+    pub file: String,
+    pub required: VersionReq,
+    /// The version in the manifest, None if the file and its archives have no version,
+    /// or if the version is declared using a file name used by several files
+    pub found: Option<Version>,
+}
+
+/// A file missing at any depth of the dependencies
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingDependency {
+    pub file: String,
+    /// The files through which it is required, from the requested file to the file declaring it
+    pub chain: Vec<String>,
+}
+
+/// The differences between the discovered and the declared dependencies of a file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DependencyDiff {
+    pub source: String,
+    /// Discovered in the content, but not declared
+    pub undeclared: Vec<String>,
+    /// Declared as a required file, but not discovered in the content
+    pub unused: Vec<String>,
+}
+
+impl DependencyDiff {
+    pub fn is_empty(&self) -> bool {
+        self.undeclared.is_empty() && self.unused.is_empty()
     }
 }
 
-#[derive(Default, Debug)]
+#[derive(Debug)]
 pub struct DependencieManager {
     deps: Vec<Dependencie>,
+    scanners: Vec<Box<dyn DependencyScanner>>,
+    /// The indexed files referenced by the content of the scanned files
+    discovered: HashMap<String, Vec<String>>,
+    /// The versions of the assets and the archives, from the version manifests
+    versions: HashMap<String, Version>,
+    /// The indexed path of the sources and the dependencies, found by update
+    resolved: HashMap<String, String>,
+}
+
+impl Default for DependencieManager {
+    fn default() -> Self {
+        Self {
+            deps: Vec::new(),
+            scanners: vec![Box::<JsonScanner>::default(), Box::<CsvScanner>::default()],
+            discovered: HashMap::new(),
+            versions: HashMap::new(),
+            resolved: HashMap::new(),
+        }
+    }
 }
 
 impl DependencieManager {
@@ -23,28 +291,55 @@ impl DependencieManager {
         Self::default()
     }
 
-    pub fn load_file(&mut self, assets_manager: &mut AssetsManager, filename: &str) {
-        let mut data = assets_manager.get(filename);
-        match data {
-            None => {
-                assets_manager.load(filename).unwrap();
-                data = assets_manager.get(filename);
-            }
-            _ => (),
+    /// Return the data of a file, loading it if needed
+    fn read(assets_manager: &mut AssetsManager, filename: &str) -> Result<Vec<u8>> {
+        let mut data = assets_manager.get(filename)?;
+        if data.is_none() {
+            assets_manager.load(filename)?;
+            data = assets_manager.get(filename)?;
         }
 
-        let content = String::from_utf8(data.unwrap()).unwrap();
-        let content = json::parse(&content).unwrap();
+        data.ok_or_else(|| Error::NotIndexed(filename.to_string()))
+    }
+
+    pub fn load_file(&mut self, assets_manager: &mut AssetsManager, filename: &str) -> Result<()> {
+        let data = Self::read(assets_manager, filename)?;
+        self.load_from_bytes(&data)
+    }
+
+    /// Same as load_file, using the content of a dependencies file not managed by the AssetsManager
+    pub fn load_from_bytes(&mut self, data: &[u8]) -> Result<()> {
+        let content = json::parse(std::str::from_utf8(data)?)?;
 
         for (name, value) in content.entries() {
             if name == "dependencies" {
                 for (name, value) in value.entries() {
-                    let mut dep = Dependencie::default();
-                    dep.source = String::from(name);
+                    let mut dep = Dependencie {
+                        source: String::from(name),
+                        ..Default::default()
+                    };
                     if value.is_array() {
                         for file in value.members() {
-                            let filename = file.as_str().unwrap().to_string();
-                            dep.deps.push(filename.clone());
+                            // Either "file" or {"pattern": "file", "optional": true, "version": "^1.2"}
+                            let (pattern, optional, version) = match file.as_str() {
+                                Some(pattern) => (Some(pattern), false, None),
+                                None => (
+                                    file["pattern"].as_str(),
+                                    file["optional"].as_bool().unwrap_or(false),
+                                    file["version"].as_str(),
+                                ),
+                            };
+                            let pattern = pattern.ok_or_else(|| {
+                                Error::Parse(format!(
+                                    "Dependency of \"{}\" is not a string or a pattern",
+                                    name
+                                ))
+                            })?;
+                            let mut pattern = DependencyPattern::parse(pattern, optional)?;
+                            if let Some(version) = version {
+                                pattern = pattern.with_version(VersionReq::parse(version)?);
+                            }
+                            dep.add(pattern);
                         }
                     }
                     self.deps.push(dep);
@@ -52,39 +347,450 @@ impl DependencieManager {
                 break;
             }
         }
+
+        Ok(())
+    }
+
+    /// Declare the dependencies of a file, like an entry of the JSON file
+    pub fn add_dependencies(&mut self, source: &str, deps: Vec<String>) {
+        self.deps.push(Dependencie {
+            source: source.to_string(),
+            deps,
+            ..Default::default()
+        });
+    }
+
+    /// Declare a dependency using a pattern
+    pub fn add_pattern(&mut self, source: &str, pattern: DependencyPattern) {
+        match self.deps.iter_mut().find(|dep| dep.source == source) {
+            Some(dep) => dep.add(pattern),
+            None => {
+                let mut dep = Dependencie {
+                    source: source.to_string(),
+                    ..Default::default()
+                };
+                dep.add(pattern);
+                self.deps.push(dep);
+            }
+        }
+    }
+
+    /// Load a version manifest, like {"versions": {"shared.zip": "1.4.0", "grass.png": "2.0.1"}}.
+    /// The version of an archive is used for the files it contains.
+    pub fn load_versions(
+        &mut self,
+        assets_manager: &mut AssetsManager,
+        filename: &str,
+    ) -> Result<()> {
+        let data = Self::read(assets_manager, filename)?;
+        self.load_versions_from_bytes(&data)
     }
 
+    /// Same as load_versions, using the content of a version manifest not managed by the AssetsManager
+    pub fn load_versions_from_bytes(&mut self, data: &[u8]) -> Result<()> {
+        let content = json::parse(std::str::from_utf8(data)?)?;
+        for (name, version) in content["versions"].entries() {
+            let version = version
+                .as_str()
+                .ok_or_else(|| Error::Parse(format!("Version of \"{}\" is not a string", name)))?;
+            self.set_version(name, Version::parse(version)?);
+        }
+        Ok(())
+    }
+
+    /// Set the version of an asset or an archive
+    pub fn set_version(&mut self, file: &str, version: Version) {
+        self.versions.insert(file.to_string(), version);
+    }
+
+    /// Return the version of a file, or of the innermost archive containing it
+    pub fn version_of(
+        &self,
+        assets_manager: &AssetsManager,
+        file: &str,
+    ) -> Result<Option<Version>> {
+        find_version(&self.versions, assets_manager, file)
+    }
+
+    /// Check the required files, and expand the patterns using the index.
+    /// A required pattern without matching file is added to the missing list,
+    /// and the files without the version required are added to the version mismatches.
     pub fn update(&mut self, assets_manager: &mut AssetsManager) {
+        // The names not indexed, or used by several files, are kept as declared
+        self.resolved.clear();
+        for name in self
+            .deps
+            .iter()
+            .flat_map(|dep| std::iter::once(&dep.source).chain(dep.deps.iter()))
+        {
+            if let Ok(path) = assets_manager.index.get_path(name) {
+                self.resolved.insert(name.clone(), path);
+            }
+        }
+
         for dep in self.deps.iter_mut() {
+            dep.missing_list.clear();
+            dep.matched.clear();
+            dep.version_mismatches.clear();
             for file in dep.deps.iter() {
-                if !assets_manager.have_file(&file) {
+                if !assets_manager.have_file(file) {
                     dep.missing_list.push(file.clone());
                 }
             }
+            for pattern in dep.patterns.iter() {
+                let files = pattern.expand(assets_manager);
+                if files.is_empty() && !pattern.optional {
+                    dep.missing_list.push(pattern.pattern.clone());
+                }
+                for file in files {
+                    if let Some(required) = pattern.version.as_ref() {
+                        // An ambiguous version is reported as a mismatch without version
+                        let found =
+                            find_version(&self.versions, assets_manager, &file).unwrap_or(None);
+                        if !found.is_some_and(|version| required.matches(&version)) {
+                            dep.version_mismatches.push(VersionMismatch {
+                                source: dep.source.clone(),
+                                file: file.clone(),
+                                required: required.clone(),
+                                found,
+                            });
+                        }
+                    }
+                    if !dep.matched.contains(&file) {
+                        dep.matched.push(file);
+                    }
+                }
+            }
         }
     }
 
-    pub fn check_if_valid(&self, filename: &str) -> bool {
-        for dep in self.deps.iter() {
-            if dep.source == filename {
-                return dep.is_valid();
+    pub fn dependencies(&self) -> &[Dependencie] {
+        &self.deps
+    }
+
+    /// Add a scanner, used before the scanners already added for the same extensions
+    pub fn add_scanner(&mut self, scanner: Box<dyn DependencyScanner>) {
+        self.scanners.insert(0, scanner);
+    }
+
+    /// Find the indexed files referenced by the content of a file, using the scanner of its extension.
+    /// The files found are recorded as discovered dependencies, and returned.
+    pub fn scan(
+        &mut self,
+        assets_manager: &mut AssetsManager,
+        filename: &str,
+    ) -> Result<Vec<String>> {
+        let extension = Path::new(filename)
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let scanner = self
+            .scanners
+            .iter()
+            .find(|scanner| scanner.extensions().contains(&extension))
+            .ok_or_else(|| Error::Unsupported(format!("No scanner for \"{}\"", filename)))?;
+
+        let data = Self::read(assets_manager, filename)?;
+        let mut found = Vec::<String>::new();
+        for value in scanner.scan(&data)? {
+            if value != filename && !found.contains(&value) && is_indexed(assets_manager, &value) {
+                found.push(value);
             }
         }
 
-        true
+        self.discovered.insert(filename.to_string(), found.clone());
+        Ok(found)
     }
 
-    pub fn get_missing_dependencies(&self, filename: &str) -> Vec<String> {
-        let mut result = Vec::<String>::new();
+    /// Return the dependencies discovered by scan
+    pub fn discovered_dependencies(&self, filename: &str) -> Option<&[String]> {
+        self.discovered.get(filename).map(Vec::as_slice)
+    }
 
-        for dep in self.deps.iter() {
-            if dep.source == filename {
-                dep.missing_list.iter().for_each(|file| {
-                    result.push(file.clone());
+    /// Compare the discovered and the declared dependencies of a scanned file.
+    /// The paths are compared from their end, so "grass.png" and "textures/grass.png" are the same file.
+    pub fn diff(&self, filename: &str) -> DependencyDiff {
+        let discovered = self.discovered.get(filename).cloned().unwrap_or_default();
+        let declared: Vec<&String> = self
+            .direct_dependencies(filename)
+            .flat_map(|dep| dep.files().chain(dep.patterns.iter().map(|p| &p.pattern)))
+            .collect();
+        let required: Vec<&String> = self
+            .direct_dependencies(filename)
+            .flat_map(|dep| dep.deps.iter())
+            .collect();
+
+        DependencyDiff {
+            source: filename.to_string(),
+            undeclared: discovered
+                .iter()
+                .filter(|file| !declared.iter().any(|declared| same_file(file, declared)))
+                .cloned()
+                .collect(),
+            unused: required
+                .into_iter()
+                .filter(|declared| !discovered.iter().any(|file| same_file(file, declared)))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Return the nodes of the graph with their size, and the edges,
+    /// a node or an edge is missing if the file was not found by update
+    fn graph<'a>(&'a self, index: &Index) -> (Vec<GraphNode>, Vec<(String, String, bool)>) {
+        let missing: HashSet<&String> = self
+            .deps
+            .iter()
+            .flat_map(|dep| dep.missing_list.iter())
+            .collect();
+        // The vectors keep the order of the output, and the sets the nodes and edges already added
+        let mut nodes = Vec::<GraphNode>::new();
+        let mut edges = Vec::<(String, String, bool)>::new();
+        let mut seen_nodes = HashSet::<&String>::new();
+        let mut seen_edges = HashSet::<(&String, &String)>::new();
+        let mut add_node = |name: &'a String| {
+            if seen_nodes.insert(name) {
+                nodes.push(GraphNode {
+                    name: name.clone(),
+                    size: file_size(index, name),
+                    missing: missing.contains(name),
                 });
             }
+        };
+
+        for dep in self.deps.iter() {
+            add_node(&dep.source);
+            let targets = dep.files().chain(
+                dep.missing_list
+                    .iter()
+                    .filter(|file| !dep.deps.contains(file)),
+            );
+            for file in targets {
+                add_node(file);
+                if seen_edges.insert((&dep.source, file)) {
+                    edges.push((dep.source.clone(), file.clone(), missing.contains(file)));
+                }
+            }
+        }
+        (nodes, edges)
+    }
+
+    /// Export the graph of the dependencies in the DOT format of Graphviz,
+    /// the missing files are red and dashed. Call update first to find the missing files.
+    pub fn export_dot(&self, index: &Index) -> String {
+        let (nodes, edges) = self.graph(index);
+        let mut output = String::from("digraph dependencies {\n");
+        for node in nodes.iter() {
+            let details = match (node.missing, node.size) {
+                (true, _) => String::from("missing"),
+                (false, Some(size)) => format!("{} B", size),
+                (false, None) => String::from("unknown size"),
+            };
+            let style = match node.missing {
+                true => ", color=red, style=dashed",
+                false => "",
+            };
+            output.push_str(&format!(
+                "    \"{}\" [label=\"{}\\n{}\"{}];\n",
+                escape_dot(&node.name),
+                escape_dot(&node.name),
+                details,
+                style
+            ));
+        }
+        for (source, target, missing) in edges.iter() {
+            let style = match missing {
+                true => " [color=red]",
+                false => "",
+            };
+            output.push_str(&format!(
+                "    \"{}\" -> \"{}\"{};\n",
+                escape_dot(source),
+                escape_dot(target),
+                style
+            ));
+        }
+        output.push_str("}\n");
+        output
+    }
+
+    /// Export the graph of the dependencies in JSON, as
+    /// {"nodes": [{"name", "size", "missing"}], "edges": [{"source", "target", "missing"}]}.
+    /// The size is null if the file is not found in the index.
+    pub fn export_json(&self, index: &Index) -> String {
+        let (nodes, edges) = self.graph(index);
+        let mut output = json::object! { nodes: [], edges: [] };
+        for node in nodes {
+            let size = match node.size {
+                Some(size) => json::JsonValue::from(size),
+                None => json::JsonValue::Null,
+            };
+            // Pushing to an array cannot fail
+            let _ = output["nodes"].push(json::object! {
+                name: node.name,
+                size: size,
+                missing: node.missing,
+            });
+        }
+        for (source, target, missing) in edges {
+            let _ = output["edges"].push(json::object! {
+                source: source,
+                target: target,
+                missing: missing,
+            });
+        }
+        output.pretty(2)
+    }
+
+    /// Return the indexed path of a file found by update, or the file itself
+    fn resolve<'a>(&'a self, file: &'a str) -> &'a str {
+        self.resolved.get(file).map_or(file, String::as_str)
+    }
+
+    /// Return the files declared as dependencies of a file, by all its entries.
+    /// The sources are compared using their indexed path, because the patterns give the indexed paths
+    fn direct_dependencies<'a>(
+        &'a self,
+        filename: &'a str,
+    ) -> impl Iterator<Item = &'a Dependencie> {
+        self.deps
+            .iter()
+            .filter(move |dep| self.resolve(&dep.source) == filename)
+    }
+
+    /// Return the indexed paths of the dependencies of a file
+    fn direct_files<'a>(&'a self, filename: &'a str) -> impl Iterator<Item = &'a str> {
+        self.direct_dependencies(filename)
+            .flat_map(|dep| dep.files())
+            .map(|file| self.resolve(file))
+    }
+
+    /// Return true if no dependency is missing, at any depth
+    pub fn check_if_valid(&self, filename: &str) -> bool {
+        self.get_missing_dependencies(filename).is_empty()
+            && self.get_version_mismatches(filename).is_empty()
+    }
+
+    /// Return the version mismatches found by update, at any depth of the dependencies of a file
+    pub fn get_version_mismatches(&self, filename: &str) -> Vec<VersionMismatch> {
+        let mut result = Vec::<VersionMismatch>::new();
+        let mut visited = HashSet::<String>::new();
+        let mut pending = vec![self.resolve(filename).to_string()];
+        while let Some(file) = pending.pop() {
+            if !visited.insert(file.clone()) {
+                continue;
+            }
+            for dep in self.direct_dependencies(&file) {
+                result.extend(dep.version_mismatches.iter().cloned());
+            }
+            pending.extend(self.direct_files(&file).map(String::from));
         }
+        result
+    }
 
+    /// Return the files missing at any depth, with the chain of files requiring them.
+    /// Each file is only visited once, so a cycle does not prevent the check.
+    pub fn get_missing_dependencies(&self, filename: &str) -> Vec<MissingDependency> {
+        let mut result = Vec::<MissingDependency>::new();
+        let mut visited = HashSet::<String>::new();
+        let mut chain = Vec::<String>::new();
+        self.collect_missing(
+            self.resolve(filename),
+            &mut chain,
+            &mut visited,
+            &mut result,
+        );
         result
     }
+
+    fn collect_missing(
+        &self,
+        filename: &str,
+        chain: &mut Vec<String>,
+        visited: &mut HashSet<String>,
+        result: &mut Vec<MissingDependency>,
+    ) {
+        if !visited.insert(filename.to_string()) {
+            return;
+        }
+
+        chain.push(filename.to_string());
+        for dep in self.direct_dependencies(filename) {
+            for file in dep.missing_list.iter() {
+                result.push(MissingDependency {
+                    file: file.clone(),
+                    chain: chain.clone(),
+                });
+            }
+            for file in dep.files() {
+                if !dep.missing_list.contains(file) {
+                    self.collect_missing(self.resolve(file), chain, visited, result);
+                }
+            }
+        }
+        chain.pop();
+    }
+
+    /// Return the dependencies of a file at any depth, each one after its own dependencies
+    pub fn get_all_dependencies(&self, filename: &str) -> Result<Vec<String>> {
+        let mut order = self.load_order(filename)?;
+        order.pop();
+        Ok(order)
+    }
+
+    /// Return the files to load before a file, in topological order, ending with the file itself.
+    /// Return Error::DependencyCycle with the chain of files if the dependencies contain a cycle.
+    pub fn load_order(&self, filename: &str) -> Result<Vec<String>> {
+        let mut order = Vec::<String>::new();
+        self.visit(
+            self.resolve(filename),
+            &mut Vec::new(),
+            &mut HashSet::new(),
+            &mut order,
+        )?;
+        Ok(order)
+    }
+
+    /// Return the first cycle found, like ["a.json", "b.json", "a.json"]
+    pub fn find_cycle(&self) -> Option<Vec<String>> {
+        let mut done = HashSet::<String>::new();
+        for dep in self.deps.iter() {
+            if let Err(Error::DependencyCycle(cycle)) = self.visit(
+                self.resolve(&dep.source),
+                &mut Vec::new(),
+                &mut done,
+                &mut Vec::new(),
+            ) {
+                return Some(cycle);
+            }
+        }
+        None
+    }
+
+    fn visit(
+        &self,
+        filename: &str,
+        chain: &mut Vec<String>,
+        done: &mut HashSet<String>,
+        order: &mut Vec<String>,
+    ) -> Result<()> {
+        if done.contains(filename) {
+            return Ok(());
+        }
+        if let Some(position) = chain.iter().position(|file| file == filename) {
+            let mut cycle = chain[position..].to_vec();
+            cycle.push(filename.to_string());
+            return Err(Error::DependencyCycle(cycle));
+        }
+
+        chain.push(filename.to_string());
+        for file in self.direct_files(filename) {
+            self.visit(file, chain, done, order)?;
+        }
+        chain.pop();
+
+        done.insert(filename.to_string());
+        order.push(filename.to_string());
+        Ok(())
+    }
 }
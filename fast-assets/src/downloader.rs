@@ -1,3 +1,4 @@
+use crate::error::{Error, Result};
 use curl::easy::Easy;
 use std::io::Write;
 
@@ -10,61 +11,67 @@ impl Downloader {
         Self::default()
     }
 
-    pub async fn download(&self, url: String, output: String) {
+    pub async fn download(&self, url: String, output: String) -> Result<()> {
         let mut easy = Easy::new();
-        match easy.url(&url) {
-            Err(err) => {
-                eprintln!("ERROR Downloader(Curl): {:?}", err);
-            }
-            _ => (),
-        }
+        easy.url(&url)?;
 
-        let file = std::fs::File::options()
+        let mut file = std::fs::File::options()
             .write(true)
             .truncate(true)
             .create(true)
-            .open(&output);
-        let mut file = match file {
-            Err(err) => {
-                panic!("ERROR Downloader(FS): {:?}", err);
-            }
-            Ok(ok) => ok,
+            .open(&output)?;
+
+        let mut write_error = None;
+        let performed = {
+            let mut transfer = easy.transfer();
+            transfer.write_function(|data| match file.write_all(data) {
+                Ok(_) => Ok(data.len()),
+                Err(err) => {
+                    write_error = Some(err);
+                    // Returning a different size than received abort the transfer
+                    Ok(0)
+                }
+            })?;
+            transfer.perform()
         };
 
-        let mut transfer = easy.transfer();
-        match transfer.write_function(move |data| {
-            file.write_all(data).unwrap();
-            file.flush().unwrap();
-            file.sync_all().unwrap();
-            Ok(data.len())
-        }) {
-            Err(err) => {
-                eprintln!("ERROR Downloader(Curl): {:?}", err);
-            }
-            _ => (),
+        if let Some(err) = write_error {
+            return Err(Error::Io(err));
         }
+        performed?;
 
-        match transfer.perform() {
-            Err(err) => {
-                eprintln!("ERROR Downloader(Curl): {:?}", err);
-            }
-            _ => (),
-        }
+        file.flush()?;
+        file.sync_all()?;
+        Ok(())
+    }
+
+    pub fn download_sync(&self, url: String, output: String) -> Result<()> {
+        pollster::block_on(self.download(url, output))
     }
 
-    pub fn download_sync(&self, url: String, output: String) {
-        pollster::block_on(self.download(url, output));
+    /// Download the file and return its content, blocking the current thread
+    pub fn download_to_memory(&self, url: &str) -> Result<Vec<u8>> {
+        let mut easy = Easy::new();
+        easy.url(url)?;
+
+        let mut data = Vec::<u8>::new();
+        {
+            let mut transfer = easy.transfer();
+            transfer.write_function(|chunk| {
+                data.extend_from_slice(chunk);
+                Ok(chunk.len())
+            })?;
+            transfer.perform()?;
+        }
+
+        Ok(data)
     }
 
     pub fn can_download(&self, target: &str) -> bool {
         let mut handle = Easy::new();
-        match handle.url(target) {
-            Ok(_) => (),
-            _ => return false,
+        if handle.url(target).is_err() {
+            return false;
         }
-        return match handle.perform() {
-            Ok(_) => true,
-            _ => false,
-        };
+        handle.perform().is_ok()
     }
 }
@@ -0,0 +1,129 @@
+use crate::handle::AssetHandle;
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+
+/// Every error that can be produced by the crate
+#[derive(Debug)]
+pub enum Error {
+    /// The file is not part of the index
+    NotIndexed(String),
+    /// The archive cannot be read, or does not contain the requested entry
+    ArchiveCorrupt(String),
+    /// An error from the file system
+    Io(std::io::Error),
+    /// A download failed
+    Download(String),
+    /// A file (index, redirect, dependencies...) has an invalid content
+    Parse(String),
+    /// A regex used as filter or search is invalid
+    Regex(regex::Error),
+    /// Trying to use the decompression cache of a file without cache path
+    CacheUnset(PathBuf),
+    /// The operation is not supported for this file
+    Unsupported(String),
+    /// The loading was stopped by an extension
+    Cancelled(String),
+    /// The handle reference a file that was removed
+    InvalidHandle(AssetHandle),
+    /// The file cannot be removed while it is acquired
+    InUse(String),
+    /// Multiple files have the requested file name
+    Ambiguous(Vec<PathBuf>),
+    /// The dependencies contain a cycle, given as the chain of files
+    DependencyCycle(Vec<String>),
+    /// The file must be loaded before this operation
+    NotLoaded(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::NotIndexed(file) => write!(f, "File \"{}\" is not indexed", file),
+            Error::ArchiveCorrupt(reason) => write!(f, "Corrupted archive: {}", reason),
+            Error::Io(err) => write!(f, "IO error: {}", err),
+            Error::Download(reason) => write!(f, "Download failed: {}", reason),
+            Error::Parse(reason) => write!(f, "Parse error: {}", reason),
+            Error::Regex(err) => write!(f, "Invalid regex: {}", err),
+            Error::CacheUnset(file) => write!(f, "Cache path undefined for \"{:?}\"", file),
+            Error::Unsupported(reason) => write!(f, "Unsupported operation: {}", reason),
+            Error::Cancelled(file) => {
+                write!(f, "Loading of \"{}\" cancelled by an extension", file)
+            }
+            Error::InvalidHandle(handle) => write!(
+                f,
+                "Invalid handle (index: {}, generation: {})",
+                handle.index(),
+                handle.generation()
+            ),
+            Error::InUse(file) => write!(f, "File \"{}\" is still acquired", file),
+            Error::Ambiguous(files) => {
+                let files: Vec<String> = files
+                    .iter()
+                    .map(|file| file.to_string_lossy().to_string())
+                    .collect();
+                write!(f, "Multiple files match: {}", files.join(", "))
+            }
+            Error::DependencyCycle(files) => {
+                write!(f, "Dependency cycle: {}", files.join(" -> "))
+            }
+            Error::NotLoaded(file) => write!(f, "File \"{}\" is not loaded", file),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Regex(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<regex::Error> for Error {
+    fn from(err: regex::Error) -> Self {
+        Error::Regex(err)
+    }
+}
+
+impl From<json::Error> for Error {
+    fn from(err: json::Error) -> Self {
+        Error::Parse(err.to_string())
+    }
+}
+
+impl From<std::string::FromUtf8Error> for Error {
+    fn from(err: std::string::FromUtf8Error) -> Self {
+        Error::Parse(err.to_string())
+    }
+}
+
+impl From<std::str::Utf8Error> for Error {
+    fn from(err: std::str::Utf8Error) -> Self {
+        Error::Parse(err.to_string())
+    }
+}
+
+impl From<zip::result::ZipError> for Error {
+    fn from(err: zip::result::ZipError) -> Self {
+        match err {
+            zip::result::ZipError::Io(err) => Error::Io(err),
+            err => Error::ArchiveCorrupt(err.to_string()),
+        }
+    }
+}
+
+impl From<curl::Error> for Error {
+    fn from(err: curl::Error) -> Self {
+        Error::Download(err.to_string())
+    }
+}
@@ -0,0 +1,130 @@
+/// Identify a file loaded in the AssetsManager,
+/// it become invalid once the file is removed, even if the slot is reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AssetHandle {
+    index: usize,
+    generation: u32,
+}
+
+impl AssetHandle {
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+#[derive(Debug)]
+struct Slot<T> {
+    generation: u32,
+    value: Option<T>,
+}
+
+/// Storage giving O(1) access using generational handles
+#[derive(Debug)]
+pub(crate) struct SlotMap<T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<usize>,
+}
+
+impl<T> Default for SlotMap<T> {
+    fn default() -> Self {
+        Self {
+            slots: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+}
+
+impl<T> SlotMap<T> {
+    pub fn insert(&mut self, value: T) -> AssetHandle {
+        match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index];
+                slot.value = Some(value);
+                AssetHandle {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    value: Some(value),
+                });
+                AssetHandle {
+                    index: self.slots.len() - 1,
+                    generation: 0,
+                }
+            }
+        }
+    }
+
+    pub fn get(&self, handle: AssetHandle) -> Option<&T> {
+        match self.slots.get(handle.index) {
+            Some(slot) if slot.generation == handle.generation => slot.value.as_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, handle: AssetHandle) -> Option<&mut T> {
+        match self.slots.get_mut(handle.index) {
+            Some(slot) if slot.generation == handle.generation => slot.value.as_mut(),
+            _ => None,
+        }
+    }
+
+    pub fn contains(&self, handle: AssetHandle) -> bool {
+        self.get(handle).is_some()
+    }
+
+    pub fn remove(&mut self, handle: AssetHandle) -> Option<T> {
+        let slot = self.slots.get_mut(handle.index)?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+        let value = slot.value.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(handle.index);
+        Some(value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (AssetHandle, &T)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            slot.value.as_ref().map(|value| {
+                (
+                    AssetHandle {
+                        index,
+                        generation: slot.generation,
+                    },
+                    value,
+                )
+            })
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (AssetHandle, &mut T)> {
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                let generation = slot.generation;
+                slot.value
+                    .as_mut()
+                    .map(|value| (AssetHandle { index, generation }, value))
+            })
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.slots.iter().filter_map(|slot| slot.value.as_ref())
+    }
+
+    /// Find the first value matching the predicate
+    pub fn find(&self, mut predicate: impl FnMut(&T) -> bool) -> Option<AssetHandle> {
+        self.iter()
+            .find(|(_, value)| predicate(value))
+            .map(|(handle, _)| handle)
+    }
+}
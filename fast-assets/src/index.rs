@@ -1,33 +1,128 @@
+use crate::archive::{self, ArchiveFormat};
+use crate::backend::{Backend, FsBackend};
+use crate::error::{Error, Result};
+use crate::index_file::{hash_content, IndexEntry, IndexFile};
+use crate::watcher::{same_path, AssetChange, ChangeKind};
 use rayon::prelude::*;
 use regex::Regex;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::io::Write;
-use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// How get_path choose between multiple files having the requested file name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResolutionPolicy {
+    /// Use the file indexed first
+    FirstIndexed,
+    /// Use the file with the fewest components in its path, the first indexed if equal
+    ShortestPath,
+    /// Return Error::Ambiguous with the files having the file name
+    #[default]
+    Error,
+}
 
 #[derive(Debug, Clone)]
 pub struct Index {
     pub root: PathBuf,
-    pub files: Vec<PathBuf>,
+    /// The files in the order they were indexed
+    files: Vec<PathBuf>,
+    /// Set of the full paths of the files
+    by_path: HashSet<PathBuf>,
+    /// The files having each file name, in the order they were indexed
+    by_name: HashMap<String, Vec<PathBuf>>,
     pub filter: Regex,
     current_pos: usize,
     csv_separator: char,
     redirect_list: HashMap<PathBuf, PathBuf>,
+    resolution_policy: ResolutionPolicy,
+    /// Add the entries of the archives to the index when searching
+    index_archives: bool,
+    compression_formats: Vec<String>,
+    backend: Arc<dyn Backend>,
+    /// Information about the files, saved in the index file
+    entries: HashMap<PathBuf, IndexEntry>,
+    /// Modification time of the directories in the root, used by rescan
+    directories: HashMap<PathBuf, Option<SystemTime>>,
 }
 
 impl Index {
-    pub fn new(root: &str, filter: &str) -> Self {
-        Self {
+    pub fn new(root: &str, filter: &str) -> Result<Self> {
+        Ok(Self {
             root: std::path::PathBuf::from(root),
             files: Vec::new(),
-            filter: Regex::new(filter).unwrap(),
+            by_path: HashSet::new(),
+            by_name: HashMap::new(),
+            filter: Regex::new(filter)?,
             current_pos: 0,
             csv_separator: ';',
             redirect_list: HashMap::new(),
-        }
+            resolution_policy: ResolutionPolicy::default(),
+            index_archives: false,
+            compression_formats: archive::default_formats(),
+            backend: Arc::new(FsBackend::default()),
+            entries: HashMap::new(),
+            directories: HashMap::new(),
+        })
+    }
+
+    /// Set the storage used to search the files
+    pub fn set_backend(&mut self, backend: Arc<dyn Backend>) {
+        self.backend = backend;
+    }
+
+    pub fn backend(&self) -> Arc<dyn Backend> {
+        self.backend.clone()
+    }
+
+    /// Add the entries of the archives found by search, as "archive.zip/entry",
+    /// only the central directory of the zip archives is read
+    pub fn set_archive_indexing(&mut self, enabled: bool) {
+        self.index_archives = enabled;
+    }
+
+    pub fn is_archive_indexing_enabled(&self) -> bool {
+        self.index_archives
+    }
+
+    /// Set the extensions of the archives, the AssetsManager keep them in sync with its compression formats
+    pub fn set_compression_formats(&mut self, formats: Vec<String>) {
+        self.compression_formats = formats;
+    }
+
+    pub(crate) fn is_archive(&self, path: &Path) -> bool {
+        let name = match path.file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => return false,
+        };
+        self.compression_formats
+            .iter()
+            .any(|format| name.ends_with(&format!(".{}", format)))
+    }
+
+    /// Return the virtual paths of the entries of an archive matching the filter
+    pub(crate) fn search_in_archive(&self, archive: &Path) -> Result<Vec<PathBuf>> {
+        let format = match ArchiveFormat::from_path(archive) {
+            Some(format) => format,
+            // Only the formats supported natively can be listed without the extensions
+            None => return Ok(Vec::new()),
+        };
+
+        let reader = self.backend.open(archive)?;
+        let mut entries: Vec<PathBuf> = archive::list_entries(format, reader)?
+            .into_iter()
+            .map(|name| archive.join(name))
+            .filter(|entry| match entry.file_name() {
+                Some(name) => self.filter.is_match(&name.to_string_lossy()),
+                None => false,
+            })
+            .collect();
+        entries.sort();
+        Ok(entries)
     }
 
-    pub fn save_as_file(&self, filename: &str) -> std::io::Result<()> {
+    pub fn save_as_file(&self, filename: &str) -> Result<()> {
         let mut output = std::fs::File::options()
             .write(true)
             .truncate(true)
@@ -45,10 +140,227 @@ impl Index {
             }
             line.pop();
             line.push('\n');
-            output.write(line.as_bytes())?;
+            output.write_all(line.as_bytes())?;
         }
 
-        output.flush().unwrap();
+        output.flush()?;
+        Ok(())
+    }
+
+    /// Save the files with their size, modification time and hash in a binary index file,
+    /// the hashes are only computed for the files modified since the last save or load
+    pub fn save_index_file(&mut self, filename: &str) -> Result<()> {
+        let mut entries = HashMap::new();
+        for file in self.files.iter() {
+            let entry = match self.backend.stat(file) {
+                Ok(metadata) if !metadata.is_dir => match self.entries.get(file) {
+                    Some(entry)
+                        if entry.len == metadata.len
+                            && entry.modified.is_some()
+                            && entry.modified == metadata.modified =>
+                    {
+                        entry.clone()
+                    }
+                    _ => IndexEntry {
+                        path: file.clone(),
+                        len: metadata.len,
+                        modified: metadata.modified,
+                        hash: hash_content(&self.backend.read(file)?),
+                    },
+                },
+                // Indexed but missing, like the files added manually
+                _ => IndexEntry {
+                    path: file.clone(),
+                    len: 0,
+                    modified: None,
+                    hash: 0,
+                },
+            };
+            entries.insert(file.clone(), entry);
+        }
+
+        let mut directories = HashMap::new();
+        if self.backend.exists(&self.root) {
+            self.scan_directories(self.root.clone(), &mut directories)?;
+        }
+
+        let index_file = IndexFile {
+            root: self.root.clone(),
+            entries: self
+                .files
+                .iter()
+                .filter_map(|file| entries.get(file).cloned())
+                .collect(),
+            directories: directories
+                .iter()
+                .map(|(path, modified)| (path.clone(), *modified))
+                .collect(),
+        };
+        std::fs::write(filename, index_file.to_bytes())?;
+
+        self.entries = entries;
+        self.directories = directories;
+        Ok(())
+    }
+
+    /// Add the files of a binary index file created by save_index_file, without searching them
+    pub fn load_index_file(&mut self, filename: &str) -> Result<()> {
+        let index_file = IndexFile::from_bytes(&std::fs::read(filename)?)?;
+        if !same_path(&index_file.root, &self.root) {
+            return Err(Error::Parse(format!(
+                "\"{}\" was created for the root \"{}\"",
+                filename,
+                index_file.root.display()
+            )));
+        }
+
+        for entry in index_file.entries {
+            self.add_file(entry.path.clone());
+            self.entries.insert(entry.path.clone(), entry);
+        }
+        self.directories.extend(index_file.directories);
+
+        Ok(())
+    }
+
+    /// Return the information about a file, known after save_index_file, load_index_file or rescan
+    pub fn file_info(&self, path: &str) -> Option<&IndexEntry> {
+        self.entries.get(&PathBuf::from(path))
+    }
+
+    /// Update the files of the root, only listing the directories modified since the index file was saved or loaded.
+    /// The files modified without adding or removing files in their directory are not detected.
+    pub fn rescan(&mut self) -> Result<Vec<AssetChange>> {
+        let mut directories = HashMap::new();
+        let mut changes = Vec::<AssetChange>::new();
+        let mut updated = Vec::<IndexEntry>::new();
+        if self.backend.exists(&self.root) {
+            self.rescan_dir(
+                self.root.clone(),
+                &mut directories,
+                &mut changes,
+                &mut updated,
+            )?;
+        }
+
+        // The files of the directories removed
+        for directory in self.directories.keys() {
+            if !directories.contains_key(directory) {
+                for path in self.entries.keys() {
+                    if path.parent() == Some(directory.as_path()) {
+                        changes.push(AssetChange {
+                            path: path.clone(),
+                            kind: ChangeKind::Removed,
+                        });
+                    }
+                }
+            }
+        }
+
+        for entry in updated {
+            self.entries.insert(entry.path.clone(), entry);
+        }
+        for change in changes.iter() {
+            match change.kind {
+                ChangeKind::Added => self.add_file(change.path.clone()),
+                ChangeKind::Modified => (),
+                ChangeKind::Removed => {
+                    self.remove_path(&change.path);
+                }
+            }
+        }
+        self.directories = directories;
+
+        changes.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(changes)
+    }
+
+    fn rescan_dir(
+        &self,
+        directory: PathBuf,
+        directories: &mut HashMap<PathBuf, Option<SystemTime>>,
+        changes: &mut Vec<AssetChange>,
+        updated: &mut Vec<IndexEntry>,
+    ) -> Result<()> {
+        let modified = self.backend.stat(&directory)?.modified;
+        let known = self.directories.get(&directory);
+        directories.insert(directory.clone(), modified);
+
+        if modified.is_some() && known == Some(&modified) {
+            // Unchanged, only the known sub directories are visited
+            for sub_directory in self.directories.keys() {
+                if sub_directory.parent() == Some(directory.as_path())
+                    && self.backend.exists(sub_directory)
+                {
+                    self.rescan_dir(sub_directory.clone(), directories, changes, updated)?;
+                }
+            }
+            return Ok(());
+        }
+
+        let mut present = HashSet::<PathBuf>::new();
+        for item in self.backend.list(&directory)? {
+            let metadata = match self.backend.stat(&item) {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            };
+            if metadata.is_dir {
+                self.rescan_dir(item, directories, changes, updated)?;
+                continue;
+            }
+            let matching = match item.file_name() {
+                Some(name) => self.filter.is_match(&name.to_string_lossy()),
+                None => false,
+            };
+            if !matching {
+                continue;
+            }
+
+            let kind = match self.entries.get(&item) {
+                None if !self.by_path.contains(&item) => ChangeKind::Added,
+                Some(entry) if entry.len == metadata.len && entry.modified == metadata.modified => {
+                    present.insert(item);
+                    continue;
+                }
+                _ => ChangeKind::Modified,
+            };
+            updated.push(IndexEntry {
+                path: item.clone(),
+                len: metadata.len,
+                modified: metadata.modified,
+                hash: hash_content(&self.backend.read(&item)?),
+            });
+            present.insert(item.clone());
+            changes.push(AssetChange { path: item, kind });
+        }
+
+        for path in self.entries.keys() {
+            if path.parent() == Some(directory.as_path()) && !present.contains(path) {
+                changes.push(AssetChange {
+                    path: path.clone(),
+                    kind: ChangeKind::Removed,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Find all the directories in a directory, with their modification time
+    fn scan_directories(
+        &self,
+        directory: PathBuf,
+        directories: &mut HashMap<PathBuf, Option<SystemTime>>,
+    ) -> Result<()> {
+        let modified = self.backend.stat(&directory)?.modified;
+        for item in self.backend.list(&directory)? {
+            if let Ok(metadata) = self.backend.stat(&item) {
+                if metadata.is_dir {
+                    self.scan_directories(item, directories)?;
+                }
+            }
+        }
+        directories.insert(directory, modified);
         Ok(())
     }
 
@@ -57,21 +369,27 @@ impl Index {
             .insert(PathBuf::from(origin), PathBuf::from(target));
     }
 
-    pub fn add_redirect_from_file(&mut self, file: &str) {
-        let content = json::parse(&std::fs::read_to_string(&file).unwrap()).unwrap();
+    pub fn add_redirect_from_file(&mut self, file: &str) -> Result<()> {
+        let content = json::parse(&std::fs::read_to_string(file)?)?;
 
-        content.entries().for_each(|entry| {
+        for entry in content.entries() {
             if entry.0 == "redirect" {
                 for redirect in entry.1.entries() {
-                    let origin = self.get_path(redirect.0).expect("Missing file in index");
-                    let target = self
-                        .get_path(redirect.1.as_str().unwrap())
-                        .expect("Missing file in index");
+                    let target = redirect.1.as_str().ok_or_else(|| {
+                        Error::Parse(format!(
+                            "Redirect target of \"{}\" is not a string",
+                            redirect.0
+                        ))
+                    })?;
+                    let origin = self.get_path(redirect.0)?;
+                    let target = self.get_path(target)?;
 
                     self.add_redirect(origin.as_str(), target.as_str());
                 }
             }
-        });
+        }
+
+        Ok(())
     }
 
     pub fn remove_redirect(&mut self, origin: &str) {
@@ -82,83 +400,149 @@ impl Index {
         self.csv_separator = new_separator;
     }
 
-    pub fn search(&mut self) {
-        self.add_files(self.search_in_dir(self.root.to_path_buf()));
+    pub fn search(&mut self) -> Result<()> {
+        let files = self.search_in_dir(self.root.to_path_buf())?;
+        self.add_files(files);
+        Ok(())
     }
 
+    /// Add a file, if it is not already indexed
     pub fn add_file(&mut self, file: PathBuf) {
+        if !self.by_path.insert(file.clone()) {
+            return;
+        }
+        if let Some(name) = file.file_name() {
+            self.by_name
+                .entry(name.to_string_lossy().to_string())
+                .or_default()
+                .push(file.clone());
+        }
         self.files.push(file);
     }
 
     pub fn add_files(&mut self, files: Vec<PathBuf>) {
-        self.files.extend(files);
+        for file in files {
+            self.add_file(file);
+        }
     }
 
     pub fn clear(&mut self) {
         self.files.clear();
+        self.by_path.clear();
+        self.by_name.clear();
+        self.entries.clear();
+        self.directories.clear();
     }
 
-    pub fn search_in_dir(&self, path: PathBuf) -> Vec<PathBuf> {
-        let read_dir = path.read_dir().unwrap();
-
-        let result = Arc::new(Mutex::new(Vec::<PathBuf>::new()));
-
-        read_dir.par_bridge().for_each(|item| {
-            let mut result = result.lock().unwrap();
-            let item = item.unwrap();
-            if item.path().is_file() {
-                if self.filter.is_match(
-                    &item
-                        .path()
-                        .file_name()
-                        .unwrap()
-                        .to_string_lossy()
-                        .to_string(),
-                ) {
-                    result.push(item.path());
-                }
-            } else if item.path().is_dir() {
-                result.append(&mut self.search_in_dir(item.path()));
+    /// Return the files in the order they were indexed
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Keep only the files matching the predicate
+    pub fn retain(&mut self, mut keep: impl FnMut(&PathBuf) -> bool) {
+        let mut removed = Vec::<PathBuf>::new();
+        self.files.retain(|file| {
+            let kept = keep(file);
+            if !kept {
+                removed.push(file.clone());
             }
+            kept
         });
+        for path in removed {
+            self.unlink(&path);
+        }
+    }
+
+    /// Remove a file using its full path, and return if it was indexed
+    fn remove_path(&mut self, path: &Path) -> bool {
+        if !self.unlink(path) {
+            return false;
+        }
+        if let Some(i) = self.files.iter().position(|file| file == path) {
+            self.files.remove(i);
+        }
+        true
+    }
+
+    /// Remove a file from the lookup tables, but not from the list of files
+    fn unlink(&mut self, path: &Path) -> bool {
+        if !self.by_path.remove(path) {
+            return false;
+        }
+        if let Some(name) = path.file_name() {
+            let name = name.to_string_lossy().to_string();
+            if let Some(files) = self.by_name.get_mut(&name) {
+                files.retain(|file| file != path);
+                if files.is_empty() {
+                    self.by_name.remove(&name);
+                }
+            }
+        }
+        self.entries.remove(path);
+        true
+    }
 
-        let result = Arc::try_unwrap(result).unwrap().into_inner().unwrap();
-        result
+    pub fn search_in_dir(&self, path: PathBuf) -> Result<Vec<PathBuf>> {
+        let mut items = self.backend.list(&path)?;
+        // Always index the files in the same order
+        items.sort();
+
+        let result = items
+            .into_par_iter()
+            .map(|item| -> Result<Vec<PathBuf>> {
+                let metadata = match self.backend.stat(&item) {
+                    Ok(metadata) => metadata,
+                    // Removed since listed
+                    Err(_) => return Ok(Vec::new()),
+                };
+                if metadata.is_dir {
+                    return self.search_in_dir(item);
+                }
+                let matching = match item.file_name() {
+                    Some(name) => self.filter.is_match(&name.to_string_lossy()),
+                    None => false,
+                };
+                let mut files = Vec::<PathBuf>::new();
+                if self.index_archives && self.is_archive(&item) {
+                    files = self.search_in_archive(&item)?;
+                }
+                if matching {
+                    files.insert(0, item);
+                }
+                Ok(files)
+            })
+            .collect::<Result<Vec<Vec<PathBuf>>>>()?;
+
+        Ok(result.into_iter().flatten().collect())
     }
 
-    pub fn add_from_file(&mut self, file: &str) {
+    pub fn add_from_file(&mut self, file: &str) -> Result<()> {
         let file = PathBuf::from(file);
-        match file.extension() {
-            Some(ext) => match ext.to_string_lossy().to_string().as_str() {
-                "csv" => {
-                    let mut csv = pro_csv::CSV::default();
-                    csv.set_sperator_char(self.csv_separator);
-                    csv.load_from_file(&file.to_string_lossy().to_string());
-                    let buffer = Arc::new(Mutex::new(Vec::<PathBuf>::new()));
-                    csv.par_bridge().for_each(|mut line| {
-                        let mut buffer = buffer.lock().unwrap();
-                        let mut path = String::new();
-                        for element in 0..line.len() - 1 {
-                            path.push_str(&format!("{}/", line[element]));
-                        }
-                        line.last_mut().unwrap().pop();
-                        path.push_str(&line.last().unwrap());
-                        buffer.push(PathBuf::from(path));
-                    });
-                    let mut buffer = buffer.lock().unwrap();
-                    self.files.append(&mut buffer);
+        if let Some(ext) = file.extension() {
+            if ext == "csv" {
+                let mut csv = pro_csv::CSV::default();
+                csv.set_sperator_char(self.csv_separator);
+                csv.load(&std::fs::read_to_string(&file)?);
+                for line in csv {
+                    let mut path = line.join("/");
+                    // Support files saved with windows line endings
+                    if path.ends_with('\r') {
+                        path.pop();
+                    }
+                    if !path.is_empty() {
+                        self.add_file(PathBuf::from(path));
+                    }
                 }
-                _ => (),
-            },
-            None => (),
+            }
         }
+
+        Ok(())
     }
 
     pub fn get_redirect(&self, filename: &str) -> Option<String> {
         for redirect in self.redirect_list.iter() {
-            if redirect.0.to_string_lossy() == filename
-                || redirect.0.file_name().unwrap().to_string_lossy() == filename
-            {
+            if redirect.0.to_string_lossy() == filename || file_name_matches(redirect.0, filename) {
                 return Some(redirect.1.to_string_lossy().to_string());
             }
         }
@@ -166,67 +550,86 @@ impl Index {
         None
     }
 
-    pub fn get_path(&self, filename: &str) -> Option<String> {
-        let redirect = self.get_redirect(filename);
-        match redirect {
-            Some(path) => return self.get_path(path.to_string().as_str()),
-            _ => (),
+    pub fn set_resolution_policy(&mut self, policy: ResolutionPolicy) {
+        self.resolution_policy = policy;
+    }
+
+    pub fn resolution_policy(&self) -> ResolutionPolicy {
+        self.resolution_policy
+    }
+
+    /// Return the path of a file using its full path or its file name.
+    /// A full path is used first, and if multiple files have the file name, the resolution policy is used.
+    pub fn get_path(&self, filename: &str) -> Result<String> {
+        if let Some(path) = self.get_redirect(filename) {
+            return self.get_path(path.as_str());
         }
 
-        let result = Arc::new(Mutex::new(Option::<String>::None));
+        let full_path = PathBuf::from(filename);
+        if self.by_path.contains(&full_path) {
+            return Ok(full_path.to_string_lossy().to_string());
+        }
 
-        self.files.par_iter().for_each(|path| {
-            let mut result = result.lock().unwrap();
-            println!("paht cmp: {}/{}", path.to_string_lossy(), filename);
-            if path.to_string_lossy() == filename
-                || path.file_name().unwrap().to_string_lossy() == filename
-            {
-                *result = Some(path.to_string_lossy().to_string());
-            }
-        });
+        let files = match self.by_name.get(filename) {
+            Some(files) if !files.is_empty() => files,
+            _ => return Err(Error::NotIndexed(filename.to_string())),
+        };
+
+        let path = match self.resolution_policy {
+            _ if files.len() == 1 => &files[0],
+            ResolutionPolicy::FirstIndexed => &files[0],
+            ResolutionPolicy::ShortestPath => files
+                .iter()
+                .min_by_key(|file| file.components().count())
+                .unwrap_or(&files[0]),
+            ResolutionPolicy::Error => return Err(Error::Ambiguous(files.clone())),
+        };
+        Ok(path.to_string_lossy().to_string())
+    }
 
-        let result = result.lock().unwrap();
-        result.clone()
+    /// Return the file names used by multiple files, with the files in the order they were indexed
+    pub fn find_duplicates(&self) -> Vec<(String, Vec<PathBuf>)> {
+        let mut duplicates: Vec<(String, Vec<PathBuf>)> = self
+            .by_name
+            .iter()
+            .filter(|(_, files)| files.len() > 1)
+            .map(|(name, files)| (name.clone(), files.clone()))
+            .collect();
+        duplicates.sort_by(|a, b| a.0.cmp(&b.0));
+        duplicates
+    }
+
+    /// Return the files with the file name, in the order they were indexed
+    pub fn files_named(&self, name: &str) -> &[PathBuf] {
+        self.by_name.get(name).map_or(&[], Vec::as_slice)
     }
 
     pub fn have_file(&self, filename: &str) -> bool {
-        let using_full_path = filename.contains("\\") || filename.contains("/");
-        for file in self.files.iter() {
-            if !using_full_path && file.file_name().unwrap().to_string_lossy() == filename {
-                return true;
-            } else if using_full_path && file.to_string_lossy() == filename {
-                return true;
-            }
+        let using_full_path = filename.contains('\\') || filename.contains('/');
+        if using_full_path {
+            self.by_path.contains(Path::new(filename))
+        } else {
+            self.by_name.contains_key(filename)
         }
-        false
     }
 
     /// Remove the index, and return if it was found
     pub fn remove_indexed_file(&mut self, filename: &str) -> bool {
-        let path = self.get_path(filename);
-        return match path {
-            Some(path) => {
-                for i in 0..self.files.len() {
-                    if self.files[i].to_string_lossy() == path {
-                        self.files.remove(i);
-                        return true;
-                    }
-                }
-                false
-            }
-            None => false,
-        };
+        match self.get_path(filename) {
+            Ok(path) => self.remove_path(Path::new(&path)),
+            Err(_) => false,
+        }
     }
 
-    pub fn regex_search(&self, filter: &str) -> Vec<PathBuf> {
+    pub fn regex_search(&self, filter: &str) -> Result<Vec<PathBuf>> {
         let mut result = Vec::<PathBuf>::new();
-        let regex = regex::Regex::new(filter).unwrap();
+        let regex = regex::Regex::new(filter)?;
         for file in self.files.iter() {
             if regex.is_match(&file.to_string_lossy()) {
                 result.push(file.clone());
             }
         }
-        result
+        Ok(result)
     }
 }
 
@@ -242,3 +645,10 @@ impl Iterator for Index {
         Some(path)
     }
 }
+
+fn file_name_matches(path: &Path, filename: &str) -> bool {
+    match path.file_name() {
+        Some(name) => name.to_string_lossy() == filename,
+        None => false,
+    }
+}
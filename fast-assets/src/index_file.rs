@@ -0,0 +1,170 @@
+use crate::error::{Error, Result};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const MAGIC: &[u8; 4] = b"FAIX";
+pub const INDEX_FILE_VERSION: u32 = 1;
+
+/// Information stored about an indexed file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexEntry {
+    pub path: PathBuf,
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+    /// FNV-1a hash of the content
+    pub hash: u64,
+}
+
+/// Content of a binary index file:
+/// the magic "FAIX", the version, the root, the files and the modification time of the directories.
+/// All the integers are little endian, and the strings are prefixed by their length.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IndexFile {
+    pub root: PathBuf,
+    pub entries: Vec<IndexEntry>,
+    pub directories: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+impl IndexFile {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut output = Vec::<u8>::new();
+        output.extend_from_slice(MAGIC);
+        output.extend_from_slice(&INDEX_FILE_VERSION.to_le_bytes());
+        write_path(&mut output, &self.root);
+
+        output.extend_from_slice(&(self.entries.len() as u64).to_le_bytes());
+        for entry in self.entries.iter() {
+            write_path(&mut output, &entry.path);
+            output.extend_from_slice(&entry.len.to_le_bytes());
+            write_time(&mut output, entry.modified);
+            output.extend_from_slice(&entry.hash.to_le_bytes());
+        }
+
+        output.extend_from_slice(&(self.directories.len() as u64).to_le_bytes());
+        for (path, modified) in self.directories.iter() {
+            write_path(&mut output, path);
+            write_time(&mut output, *modified);
+        }
+
+        output
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut reader = Reader { data, pos: 0 };
+        if reader.take(4)? != MAGIC {
+            return Err(Error::Parse(String::from("Not an index file")));
+        }
+        let version = reader.u32()?;
+        if version != INDEX_FILE_VERSION {
+            return Err(Error::Parse(format!(
+                "Unsupported index file version {}, expected {}",
+                version, INDEX_FILE_VERSION
+            )));
+        }
+
+        let mut index_file = IndexFile {
+            root: reader.path()?,
+            ..Default::default()
+        };
+
+        for _ in 0..reader.u64()? {
+            index_file.entries.push(IndexEntry {
+                path: reader.path()?,
+                len: reader.u64()?,
+                modified: reader.time()?,
+                hash: reader.u64()?,
+            });
+        }
+
+        for _ in 0..reader.u64()? {
+            let path = reader.path()?;
+            index_file.directories.push((path, reader.time()?));
+        }
+
+        if reader.pos != data.len() {
+            return Err(Error::Parse(String::from(
+                "Unexpected data at the end of the index file",
+            )));
+        }
+        Ok(index_file)
+    }
+}
+
+/// Hash the content of a file using FNV-1a (64 bits)
+pub fn hash_content(data: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in data {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+fn write_path(output: &mut Vec<u8>, path: &std::path::Path) {
+    let path = path.to_string_lossy();
+    output.extend_from_slice(&(path.len() as u32).to_le_bytes());
+    output.extend_from_slice(path.as_bytes());
+}
+
+fn write_time(output: &mut Vec<u8>, time: Option<SystemTime>) {
+    match time.and_then(|time| time.duration_since(UNIX_EPOCH).ok()) {
+        Some(duration) => {
+            output.push(1);
+            output.extend_from_slice(&duration.as_secs().to_le_bytes());
+            output.extend_from_slice(&duration.subsec_nanos().to_le_bytes());
+        }
+        None => output.push(0),
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8]> {
+        match self.data.get(self.pos..self.pos + len) {
+            Some(bytes) => {
+                self.pos += len;
+                Ok(bytes)
+            }
+            None => Err(Error::Parse(String::from("Truncated index file"))),
+        }
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn path(&mut self) -> Result<PathBuf> {
+        let len = self.u32()? as usize;
+        let path = std::str::from_utf8(self.take(len)?)?;
+        Ok(PathBuf::from(path))
+    }
+
+    fn time(&mut self) -> Result<Option<SystemTime>> {
+        match self.take(1)?[0] {
+            0 => Ok(None),
+            _ => {
+                let secs = self.u64()?;
+                let nanos = self.u32()?;
+                if nanos >= 1_000_000_000 {
+                    return Err(Error::Parse(String::from("Invalid time in the index file")));
+                }
+                match UNIX_EPOCH.checked_add(Duration::new(secs, nanos)) {
+                    Some(time) => Ok(Some(time)),
+                    None => Err(Error::Parse(String::from("Invalid time in the index file"))),
+                }
+            }
+        }
+    }
+}
@@ -1,9 +1,24 @@
+pub mod archive;
+pub mod backend;
+pub mod background;
+pub mod codec;
 pub mod decompression_manager;
 pub mod dependencie_manager;
 pub mod downloader;
+pub mod error;
 pub mod extension;
+pub mod handle;
 pub mod index;
+pub mod index_file;
+pub mod loader;
 pub mod manager;
+pub mod mount;
+pub mod packer;
+pub mod scanner;
+pub mod version;
+pub mod watcher;
+
+pub use error::{Error, Result};
 
 #[cfg(test)]
 mod test {
@@ -11,34 +26,34 @@ mod test {
 
     #[test]
     pub fn get_cargo_toml_path_index() {
-        let mut index = crate::index::Index::new("./", "Cargo.toml");
-        index.search();
+        let mut index = crate::index::Index::new("./", "Cargo.toml").unwrap();
+        index.search().unwrap();
         assert_eq!(
-            index.get_path("./Cargo.toml"),
+            index.get_path("./Cargo.toml").ok(),
             Some(String::from("./Cargo.toml"))
         );
     }
 
     #[test]
     pub fn get_cargo_toml_path_index_using_custom() {
-        let mut index = crate::index::Index::new("./", "____________");
+        let mut index = crate::index::Index::new("./", "____________").unwrap();
         index.set_csv_separator('/');
-        index.search();
-        index.add_from_file("test_resources/index.csv");
+        index.search().unwrap();
+        index.add_from_file("test_resources/index.csv").unwrap();
         assert_eq!(
-            index.get_path("Cargo.toml"),
+            index.get_path("Cargo.toml").ok(),
             Some(String::from("Cargo.toml"))
         );
     }
 
     #[test]
     pub fn get_compressed_file_path() {
-        let mut index = crate::index::Index::new("./", "____________");
+        let mut index = crate::index::Index::new("./", "____________").unwrap();
         index.set_csv_separator('/');
-        index.search();
-        index.add_from_file("test_resources/index.csv");
+        index.search().unwrap();
+        index.add_from_file("test_resources/index.csv").unwrap();
         assert_eq!(
-            index.get_path("index.json"),
+            index.get_path("index.json").ok(),
             Some(String::from("index/index.zip/index.json"))
         );
     }
@@ -47,7 +62,7 @@ mod test {
     pub fn get_distant_file_using_asset_manager() {
         let mut asset_manager = crate::manager::AssetsManager::default();
         asset_manager.load("https://www.rust-lang.org/").unwrap();
-        let downloaded_file = asset_manager.get("https://www.rust-lang.org/");
+        let downloaded_file = asset_manager.get("https://www.rust-lang.org/").unwrap();
 
         let content = match downloaded_file {
             None => String::from(""),
@@ -56,88 +71,511 @@ mod test {
 
         println!("File content: {}", content);
 
-        assert!(content != "", "Failed to download the file.");
+        assert!(!content.is_empty(), "Failed to download the file.");
     }
 
     #[test]
     pub fn get_data() {
-        let mut index = crate::index::Index::new("./", "____________");
+        let mut index = crate::index::Index::new("./", "____________").unwrap();
         index.set_csv_separator('/');
-        index.search();
-        index.add_from_file("test_resources/index.csv");
+        index.search().unwrap();
+        index.add_from_file("test_resources/index.csv").unwrap();
 
         let dc = crate::decompression_manager::DecompressionManager::default();
 
         let mut manager = crate::manager::AssetsManager::new(index, dc);
         manager.load("index.csv").unwrap();
-        assert_ne!(manager.get("index.csv"), None);
+        assert_ne!(manager.get("index.csv").unwrap(), None);
 
         manager.load("index.csv").unwrap();
-        assert_ne!(manager.get_ref("index.csv"), None);
-        assert_ne!(manager.get_ref("index.csv").unwrap().clone(), None);
+        assert_ne!(manager.get_ref("index.csv").unwrap(), None);
+        assert_ne!(manager.get_ref("index.csv").unwrap().unwrap().clone(), None);
 
         manager.load("index.csv").unwrap();
-        assert_ne!(manager.get_mut("index.csv"), None);
-        assert_ne!(manager.get_mut("index.csv").unwrap().clone(), None);
+        assert_ne!(manager.get_mut("index.csv").unwrap(), None);
+        assert_ne!(manager.get_mut("index.csv").unwrap().unwrap().clone(), None);
     }
 
     #[test]
     pub fn dependencie_manager() {
-        let mut index = crate::index::Index::new("./", "____________");
+        let mut index = crate::index::Index::new("./", "____________").unwrap();
         index.set_csv_separator('/');
-        index.search();
-        index.add_from_file("test_resources/index.csv");
+        index.search().unwrap();
+        index.add_from_file("test_resources/index.csv").unwrap();
 
         let dc = crate::decompression_manager::DecompressionManager::default();
 
         let mut manager = crate::manager::AssetsManager::new(index, dc);
 
         let mut deps = crate::dependencie_manager::DependencieManager::default();
-        deps.load_file(&mut manager, "test_resources/deps.json");
+        deps.load_file(&mut manager, "test_resources/deps.json")
+            .unwrap();
+        deps.update(&mut manager);
+
+        assert!(!deps.get_missing_dependencies("index.csv").is_empty());
+    }
+
+    #[test]
+    pub fn dependency_graph() {
+        let mut index = crate::index::Index::new("./", ".*").unwrap();
+        for file in ["a.json", "b.json", "c.png", "e.png"] {
+            index.add_file(PathBuf::from(file));
+        }
+        let mut manager = crate::manager::AssetsManager::new(index, Default::default());
+
+        let mut deps = crate::dependencie_manager::DependencieManager::new();
+        let files = |files: &[&str]| files.iter().map(|file| file.to_string()).collect();
+        deps.add_dependencies("a.json", files(&["b.json", "c.png"]));
+        deps.add_dependencies("b.json", files(&["c.png", "d.png"]));
+        deps.add_dependencies("c.png", files(&["e.png"]));
+        deps.update(&mut manager);
+        deps.update(&mut manager);
+
+        assert_eq!(
+            deps.load_order("a.json").unwrap(),
+            vec!["e.png", "c.png", "d.png", "b.json", "a.json"]
+        );
+        assert_eq!(
+            deps.get_all_dependencies("b.json").unwrap(),
+            vec!["e.png", "c.png", "d.png"]
+        );
+        assert_eq!(deps.find_cycle(), None);
+
+        // Missing at any depth, with the files requiring it
+        let missing = deps.get_missing_dependencies("a.json");
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].file, "d.png");
+        assert_eq!(missing[0].chain, vec!["a.json", "b.json"]);
+        assert!(!deps.check_if_valid("a.json"));
+        assert!(deps.check_if_valid("c.png"));
+
+        deps.add_dependencies("e.png", files(&["b.json"]));
+        let cycle = vec!["b.json", "c.png", "e.png", "b.json"];
+        assert_eq!(deps.find_cycle().unwrap(), cycle);
+        assert!(matches!(
+            deps.load_order("b.json"),
+            Err(crate::Error::DependencyCycle(chain)) if chain == cycle
+        ));
+        assert_eq!(deps.get_missing_dependencies("e.png").len(), 1);
+    }
+
+    #[test]
+    pub fn load_with_dependencies() {
+        let dir = TestDir::new("load_with_dependencies");
+        let root = dir.path();
+        for file in ["level1.json", "level2.json", "grass.png", "stone.png"] {
+            std::fs::write(format!("{}/{}", root, file), file).unwrap();
+        }
+
+        let mut index = crate::index::Index::new(root, ".*").unwrap();
+        index.search().unwrap();
+        let mut manager = crate::manager::AssetsManager::new(index, Default::default());
+        let mut deps = crate::dependencie_manager::DependencieManager::new();
+        let files = |files: &[&str]| files.iter().map(|file| file.to_string()).collect();
+        deps.add_dependencies("level1.json", files(&["grass.png", "stone.png"]));
+        deps.add_dependencies("level2.json", files(&["stone.png"]));
+        deps.add_dependencies("level3.json", files(&["grass.png", "missing.png"]));
+
+        let level1 = manager
+            .load_with_dependencies("level1.json", &deps)
+            .unwrap();
+        assert_eq!(
+            manager.get_by_handle(level1).unwrap().clone().unwrap(),
+            b"level1.json"
+        );
+        manager
+            .load_with_dependencies("level2.json", &deps)
+            .unwrap();
+        let stone = manager.find_file_index("stone.png").unwrap();
+        assert_eq!(manager.ref_count(stone), 2);
+
+        // The stone is still used by the second level
+        let unloaded = manager
+            .unload_with_dependencies("level1.json", &deps)
+            .unwrap();
+        assert_eq!(unloaded, vec!["level1.json", "grass.png"]);
+        assert_eq!(manager.ref_count(stone), 1);
+        assert_ne!(manager.get("stone.png").unwrap(), None);
+
+        // Nothing stays acquired when a dependency is missing
+        assert!(manager
+            .load_with_dependencies("level3.json", &deps)
+            .is_err());
+        let grass = manager.find_file_index("grass.png").unwrap();
+        assert_eq!(manager.ref_count(grass), 0);
+        assert_eq!(manager.get("grass.png").unwrap(), None);
+    }
+
+    #[test]
+    pub fn dependency_patterns() {
+        use crate::dependencie_manager::{DependencyPattern, PatternKind};
+
+        let dir = TestDir::new("dependency_patterns");
+        let root = dir.path();
+        std::fs::create_dir_all(format!("{}/textures/level1/trees", root)).unwrap();
+        for file in [
+            "level1.json",
+            "textures/level1/grass.png",
+            "textures/level1/stone.png",
+            "textures/level1/trees/oak.png",
+            "textures/level1/notes.txt",
+        ] {
+            std::fs::write(format!("{}/{}", root, file), b"").unwrap();
+        }
+        std::fs::write(
+            format!("{}/deps.json", root),
+            r#"{"dependencies": {"level1.json": [
+                "textures/level1/*.png",
+                "regex:trees/[a-z]+\\.png$",
+                {"pattern": "textures/level2/*.png", "optional": true},
+                {"pattern": "music.ogg", "optional": true},
+                "sounds/**/*.ogg"
+            ]}}"#,
+        )
+        .unwrap();
+
+        let mut index = crate::index::Index::new(root, ".*").unwrap();
+        index.search().unwrap();
+        let mut manager = crate::manager::AssetsManager::new(index, Default::default());
+        let mut deps = crate::dependencie_manager::DependencieManager::new();
+        deps.load_file(&mut manager, &format!("{}/deps.json", root))
+            .unwrap();
+        deps.update(&mut manager);
+
+        let level1 = &deps.dependencies()[0];
+        let mut matched = level1.matched.clone();
+        matched.sort();
+        assert_eq!(
+            matched,
+            vec![
+                format!("{}/textures/level1/grass.png", root),
+                format!("{}/textures/level1/stone.png", root),
+                format!("{}/textures/level1/trees/oak.png", root),
+            ]
+        );
+        // Only the required pattern without match is missing
+        assert_eq!(level1.missing_list, vec!["sounds/**/*.ogg"]);
+        assert!(!deps.check_if_valid("level1.json"));
+
+        let glob = DependencyPattern::parse("textures/**/*.png", false).unwrap();
+        assert_eq!(glob.kind, PatternKind::Glob);
+        deps.add_pattern("level2.json", glob);
+        deps.update(&mut manager);
+        assert_eq!(deps.dependencies()[1].matched.len(), 3);
+        assert!(deps.check_if_valid("level2.json"));
+
+        assert!(DependencyPattern::parse("regex:(", false).is_err());
+
+        // The files matched by a pattern have their own dependencies, declared using their name
+        std::fs::create_dir_all(format!("{}/maps", root)).unwrap();
+        for file in ["maps/x.json", "maps/y.png"] {
+            std::fs::write(format!("{}/{}", root, file), b"").unwrap();
+        }
+        manager.index.search().unwrap();
+        deps.add_pattern(
+            "level3.json",
+            DependencyPattern::parse("maps/*.json", false).unwrap(),
+        );
+        deps.add_dependencies(
+            "x.json",
+            vec![String::from("y.png"), String::from("zzz.png")],
+        );
+        deps.update(&mut manager);
+        assert_eq!(
+            deps.load_order("level3.json").unwrap(),
+            vec![
+                format!("{}/maps/y.png", root),
+                String::from("zzz.png"),
+                format!("{}/maps/x.json", root),
+                String::from("level3.json"),
+            ]
+        );
+        let missing = deps.get_missing_dependencies("level3.json");
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].file, "zzz.png");
+        assert_eq!(
+            missing[0].chain,
+            vec![String::from("level3.json"), format!("{}/maps/x.json", root)]
+        );
+    }
+
+    #[test]
+    pub fn dependency_same_names() {
+        let dir = TestDir::new("dependency_same_names");
+        let root = dir.path();
+        for file in [
+            "config.json",
+            "data/config.json",
+            "base/level.json",
+            "base/base.png",
+            "mods/level.json",
+            "mods/mods.png",
+        ] {
+            let path = format!("{}/{}", root, file);
+            std::fs::create_dir_all(std::path::Path::new(&path).parent().unwrap()).unwrap();
+            std::fs::write(path, b"").unwrap();
+        }
+
+        let mut index = crate::index::Index::new(root, ".*").unwrap();
+        index.search().unwrap();
+        index.set_resolution_policy(crate::index::ResolutionPolicy::ShortestPath);
+        let mut manager = crate::manager::AssetsManager::new(index, Default::default());
+        let mut deps = crate::dependencie_manager::DependencieManager::new();
+        // A file depending on another file with the same name is not a cycle
+        deps.add_dependencies("config.json", vec![String::from("data/config.json")]);
+        for dir in ["base", "mods"] {
+            deps.add_dependencies(
+                &format!("{}/{}/level.json", root, dir),
+                vec![format!("{}/{}/{}.png", root, dir, dir)],
+            );
+        }
+        deps.update(&mut manager);
+
+        assert!(deps.find_cycle().is_none());
+        assert_eq!(
+            deps.load_order("config.json").unwrap(),
+            vec![
+                String::from("data/config.json"),
+                format!("{}/config.json", root),
+            ]
+        );
+        // The dependencies of the files with the same name are not merged
+        assert_eq!(
+            deps.load_order(&format!("{}/mods/level.json", root))
+                .unwrap(),
+            vec![
+                format!("{}/mods/mods.png", root),
+                format!("{}/mods/level.json", root),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn dependency_discovery() {
+        let dir = TestDir::new("dependency_discovery");
+        let root = dir.path();
+        std::fs::create_dir_all(format!("{}/textures", root)).unwrap();
+        for file in ["textures/grass.png", "textures/stone.png", "music.ogg"] {
+            std::fs::write(format!("{}/{}", root, file), b"").unwrap();
+        }
+        std::fs::write(
+            format!("{}/level1.json", root),
+            r#"{"name": "level1.json", "tiles": [{"texture": "grass.png"}, {"texture": "textures/stone.png"}], "music": 3}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            format!("{}/music.csv", root),
+            "level1;music.ogg\nlevel2;missing.ogg",
+        )
+        .unwrap();
+
+        let mut index = crate::index::Index::new(root, ".*").unwrap();
+        index.search().unwrap();
+        let mut manager = crate::manager::AssetsManager::new(index, Default::default());
+        let mut deps = crate::dependencie_manager::DependencieManager::new();
+        deps.add_dependencies(
+            "level1.json",
+            vec![String::from("grass.png"), String::from("old.png")],
+        );
+
+        assert_eq!(
+            deps.scan(&mut manager, "level1.json").unwrap(),
+            vec!["grass.png", "textures/stone.png"]
+        );
+        let diff = deps.diff("level1.json");
+        assert_eq!(diff.undeclared, vec!["textures/stone.png"]);
+        assert_eq!(diff.unused, vec!["old.png"]);
+
+        assert_eq!(
+            deps.scan(&mut manager, "music.csv").unwrap(),
+            vec!["music.ogg"]
+        );
+        assert_eq!(
+            deps.discovered_dependencies("music.csv").unwrap(),
+            &[String::from("music.ogg")]
+        );
+        assert!(matches!(
+            deps.scan(&mut manager, "music.ogg"),
+            Err(crate::Error::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    pub fn dependency_export() {
+        let mut index = crate::index::Index::new("./", ".*").unwrap();
+        index.add_file(PathBuf::from("test_resources/index.csv"));
+        let size = std::fs::metadata("test_resources/index.csv").unwrap().len();
+        let mut manager = crate::manager::AssetsManager::new(index, Default::default());
+        let mut deps = crate::dependencie_manager::DependencieManager::new();
+        deps.add_dependencies(
+            "level\"1\".json",
+            vec![String::from("index.csv"), String::from("other.csv")],
+        );
         deps.update(&mut manager);
 
-        assert_eq!(deps.get_missing_dependencies("index.csv").is_empty(), false);
+        let dot = deps.export_dot(&manager.index);
+        assert!(dot.starts_with("digraph dependencies {"));
+        assert!(dot.contains(&format!(r#""index.csv" [label="index.csv\n{} B"];"#, size)));
+        assert!(
+            dot.contains(r#""other.csv" [label="other.csv\nmissing", color=red, style=dashed];"#)
+        );
+        assert!(dot.contains(r#""level\"1\".json" -> "other.csv" [color=red];"#));
+
+        let graph = json::parse(&deps.export_json(&manager.index)).unwrap();
+        assert_eq!(graph["nodes"].len(), 3);
+        assert!(graph["nodes"][0]["size"].is_null());
+        assert_eq!(graph["nodes"][1]["size"].as_u64(), Some(size));
+        assert_eq!(graph["nodes"][2]["missing"], true);
+        assert_eq!(graph["edges"][1]["target"], "other.csv");
+        assert_eq!(graph["edges"][1]["missing"], true);
+    }
+
+    #[test]
+    pub fn versioned_dependencies() {
+        use crate::version::{Version, VersionReq};
+
+        let req = |text: &str| VersionReq::parse(text).unwrap();
+        let version = |text: &str| Version::parse(text).unwrap();
+        assert!(req("^1.2").matches(&version("1.9.0")));
+        assert!(!req("^1.2").matches(&version("2.0.0")));
+        assert!(!req("^0.2.1").matches(&version("0.3.0")));
+        assert!(req("~1.4").matches(&version("1.4.7")));
+        assert!(!req("~1.4").matches(&version("1.5.0")));
+        assert!(req(">=1.0, <2.0").matches(&version("1.5")));
+        assert!(req("=2").matches(&version("2.3.1")));
+        assert!(req("*").matches(&version("0.0.1")));
+        assert!(VersionReq::parse("^1.x").is_err());
+
+        let dir = TestDir::new("versioned_dependencies");
+        let root = dir.path();
+        write_zip(
+            &format!("{}/shared.zip", root),
+            &[("grass.png", b"grass"), ("stone.png", b"stone")],
+        );
+        std::fs::write(format!("{}/dlc.json", root), b"{}").unwrap();
+        std::fs::write(format!("{}/music.ogg", root), b"").unwrap();
+        std::fs::write(
+            format!("{}/deps.json", root),
+            r#"{"dependencies": {"dlc.json": [
+                {"pattern": "grass.png", "version": "^1.2"},
+                {"pattern": "stone.png", "version": ">=2.0"},
+                {"pattern": "music.ogg", "version": "1"}
+            ]}}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            format!("{}/versions.json", root),
+            r#"{"versions": {"shared.zip": "1.4.0", "stone.png": "1.0.0"}}"#,
+        )
+        .unwrap();
+
+        let mut index = crate::index::Index::new(root, ".*").unwrap();
+        index.set_archive_indexing(true);
+        index.search().unwrap();
+        let mut manager = crate::manager::AssetsManager::new(index, Default::default());
+        let mut deps = crate::dependencie_manager::DependencieManager::new();
+        deps.load_file(&mut manager, &format!("{}/deps.json", root))
+            .unwrap();
+        deps.load_versions(&mut manager, &format!("{}/versions.json", root))
+            .unwrap();
+        deps.update(&mut manager);
+
+        // The version of the archive is used for the grass, the stone has its own version
+        assert_eq!(
+            deps.version_of(&manager, "grass.png").unwrap(),
+            Some(version("1.4.0"))
+        );
+        let mismatches = deps.get_version_mismatches("dlc.json");
+        let found: Vec<(&str, Option<Version>)> = mismatches
+            .iter()
+            .map(|mismatch| (mismatch.file.as_str(), mismatch.found))
+            .collect();
+        assert_eq!(
+            found,
+            vec![("stone.png", Some(version("1.0.0"))), ("music.ogg", None)]
+        );
+        assert!(deps.dependencies()[0].missing_list.is_empty());
+        assert!(!deps.check_if_valid("dlc.json"));
+
+        deps.set_version("stone.png", version("2.1.0"));
+        deps.set_version("music.ogg", version("1.0.0"));
+        deps.update(&mut manager);
+        assert!(deps.check_if_valid("dlc.json"));
+
+        // A version declared using a file name used by several files is ambiguous
+        std::fs::create_dir_all(format!("{}/old", root)).unwrap();
+        for file in ["wood.png", "old/wood.png"] {
+            std::fs::write(format!("{}/{}", root, file), b"").unwrap();
+        }
+        manager.index.search().unwrap();
+        deps.set_version("wood.png", version("1.0.0"));
+        let wood = format!("{}/old/wood.png", root);
+        assert!(matches!(
+            deps.version_of(&manager, &wood),
+            Err(crate::error::Error::Ambiguous(_))
+        ));
+        deps.set_version(&wood, version("0.9.0"));
+        assert_eq!(
+            deps.version_of(&manager, &wood).unwrap(),
+            Some(version("0.9.0"))
+        );
     }
 
     #[test]
     pub fn redirect() {
-        let mut index = crate::index::Index::new("./", "____________");
+        let mut index = crate::index::Index::new("./", "____________").unwrap();
         index.set_csv_separator('/');
-        index.search();
-        index.add_from_file("test_resources/index.csv");
-        index.add_redirect_from_file("test_resources/redirect.json");
+        index.search().unwrap();
+        index.add_from_file("test_resources/index.csv").unwrap();
+        index
+            .add_redirect_from_file("test_resources/redirect.json")
+            .unwrap();
         assert_eq!(
-            index.get_path("Cargo.toml"),
+            index.get_path("Cargo.toml").ok(),
             Some(String::from("other.toml"))
         );
     }
 
     #[test]
     pub fn get_compressed() {
-        let mut index = crate::index::Index::new("./", "____________");
+        let mut index = crate::index::Index::new("./", "____________").unwrap();
         index.set_csv_separator('/');
-        index.search();
-        index.add_from_file("test_resources/index.csv");
+        index.search().unwrap();
+        index.add_from_file("test_resources/index.csv").unwrap();
 
         let dc = crate::decompression_manager::DecompressionManager::default();
 
         let mut manager = crate::manager::AssetsManager::new(index, dc);
         manager.load("localization.csv").unwrap();
-        assert_ne!(manager.get("localization.csv"), None);
+        assert_ne!(manager.get("localization.csv").unwrap(), None);
 
-        assert_ne!(manager.get_ref("localization.csv"), None);
-        assert_ne!(manager.get_ref("localization.csv").unwrap().clone(), None);
+        assert_ne!(manager.get_ref("localization.csv").unwrap(), None);
+        assert_ne!(
+            manager
+                .get_ref("localization.csv")
+                .unwrap()
+                .unwrap()
+                .clone(),
+            None
+        );
 
-        assert_ne!(manager.get_mut("localization.csv"), None);
-        assert_ne!(manager.get_mut("localization.csv").unwrap().clone(), None);
+        assert_ne!(manager.get_mut("localization.csv").unwrap(), None);
+        assert_ne!(
+            manager
+                .get_mut("localization.csv")
+                .unwrap()
+                .unwrap()
+                .clone(),
+            None
+        );
     }
 
     #[test]
-    pub fn saving() -> std::io::Result<()> {
-        let mut index = crate::index::Index::new("./", "____________");
+    pub fn saving() -> crate::Result<()> {
+        let mut index = crate::index::Index::new("./", "____________").unwrap();
         index.set_csv_separator('/');
-        index.search();
-        index.add_from_file("test_resources/index.csv");
+        index.search().unwrap();
+        index.add_from_file("test_resources/index.csv").unwrap();
 
         let dc = crate::decompression_manager::DecompressionManager::default();
 
@@ -147,18 +585,18 @@ mod test {
     }
 
     #[test]
-    pub fn create() -> std::io::Result<()> {
-        let mut index = crate::index::Index::new("./", "____________");
+    pub fn create() -> crate::Result<()> {
+        let mut index = crate::index::Index::new("./", "____________").unwrap();
         index.set_csv_separator('/');
-        index.search();
-        index.add_from_file("test_resources/index.csv");
+        index.search().unwrap();
+        index.add_from_file("test_resources/index.csv").unwrap();
 
         let dc = crate::decompression_manager::DecompressionManager::default();
 
         let mut manager = crate::manager::AssetsManager::new(index, dc);
         manager.create_file("myFile.txt")?;
         manager.load("myFile.txt")?;
-        assert_ne!(manager.get("myFile.txt"), None);
+        assert_ne!(manager.get("myFile.txt").unwrap(), None);
         Ok(())
     }
 
@@ -178,9 +616,9 @@ mod test {
         assert!(downloader.can_download(&link_b));
         assert!(downloader.can_download(&link_c));
 
-        downloader.download_sync(link_a, out_a.clone());
-        downloader.download_sync(link_b, out_b.clone());
-        downloader.download_sync(link_c, out_c.clone());
+        downloader.download_sync(link_a, out_a.clone()).unwrap();
+        downloader.download_sync(link_b, out_b.clone()).unwrap();
+        downloader.download_sync(link_c, out_c.clone()).unwrap();
 
         assert!(PathBuf::from(out_a).exists());
         assert!(PathBuf::from(out_b).exists());
@@ -189,22 +627,22 @@ mod test {
 
     #[test]
     pub fn file_control() {
-        let mut index = crate::index::Index::new("./", "____________");
+        let mut index = crate::index::Index::new("./", "____________").unwrap();
         index.set_csv_separator('/');
-        index.search();
-        index.add_from_file("test_resources/index.csv");
+        index.search().unwrap();
+        index.add_from_file("test_resources/index.csv").unwrap();
 
         let dc = crate::decompression_manager::DecompressionManager::default();
 
         let mut manager = crate::manager::AssetsManager::new(index, dc);
         manager.create_file("demoFile.txt").unwrap();
-        assert_ne!(manager.index.get_path("demoFile.txt"), None);
+        assert_ne!(manager.index.get_path("demoFile.txt").ok(), None);
 
         manager
             .copy_file("demoFile.txt", "index/demoFile.txt")
             .unwrap();
         assert_eq!(
-            manager.index.get_path("demoFile.txt"),
+            manager.index.get_path("demoFile.txt").ok(),
             Some(String::from("index/demoFile.txt"))
         );
 
@@ -215,14 +653,14 @@ mod test {
             .move_file("index/demoFile.txt", "demoFile.txt")
             .unwrap();
         assert_eq!(
-            manager.index.get_path("demoFile.txt"),
+            manager.index.get_path("demoFile.txt").ok(),
             Some(String::from("demoFile.txt"))
         );
 
         assert!(!path.exists());
 
         manager.remove_file("demoFile.txt").unwrap();
-        assert_eq!(manager.index.get_path("demoFile.txt"), None);
+        assert_eq!(manager.index.get_path("demoFile.txt").ok(), None);
 
         let path = PathBuf::from("demoFile.txt");
 
@@ -231,22 +669,1079 @@ mod test {
 
     #[test]
     pub fn set_data() {
-        let mut index = crate::index::Index::new("./", "____________");
+        let mut index = crate::index::Index::new("./", "____________").unwrap();
         index.set_csv_separator('/');
-        index.search();
-        index.add_from_file("test_resources/index.csv");
+        index.search().unwrap();
+        index.add_from_file("test_resources/index.csv").unwrap();
 
         let dc = crate::decompression_manager::DecompressionManager::default();
 
         let mut manager = crate::manager::AssetsManager::new(index, dc);
         manager.create_file("demoFile.txt").unwrap();
-        assert_ne!(manager.index.get_path("demoFile.txt"), None);
+        assert_ne!(manager.index.get_path("demoFile.txt").ok(), None);
+        assert!(matches!(
+            manager.set_data("demoFile.txt", Vec::new()),
+            Err(crate::Error::NotLoaded(_))
+        ));
 
         manager.load("demoFile.txt").unwrap();
-        manager.set_data("demoFile.txt", b"Hello, World!".to_vec());
+        manager
+            .set_data("demoFile.txt", b"Hello, World!".to_vec())
+            .unwrap();
         assert_eq!(
-            String::from_utf8(manager.get_mut("demoFile.txt").unwrap().clone().unwrap()).unwrap(),
+            String::from_utf8(
+                manager
+                    .get_mut("demoFile.txt")
+                    .unwrap()
+                    .unwrap()
+                    .clone()
+                    .unwrap()
+            )
+            .unwrap(),
             String::from("Hello, World!")
         );
     }
+
+    #[test]
+    pub fn errors() {
+        assert!(matches!(
+            crate::index::Index::new("./", "(unclosed"),
+            Err(crate::Error::Regex(_))
+        ));
+
+        let mut manager = crate::manager::AssetsManager::default();
+        assert!(matches!(
+            manager.load("not_existing_file.txt"),
+            Err(crate::Error::NotIndexed(_))
+        ));
+        assert!(matches!(
+            manager.get("not_existing_file.txt"),
+            Err(crate::Error::NotIndexed(_))
+        ));
+    }
+
+    #[test]
+    pub fn handles() {
+        let mut index = crate::index::Index::new("./", "____________").unwrap();
+        index.set_csv_separator('/');
+        index.add_from_file("test_resources/index.csv").unwrap();
+
+        let dc = crate::decompression_manager::DecompressionManager::default();
+
+        let mut manager = crate::manager::AssetsManager::new(index, dc);
+        let handle = manager.load("index.csv").unwrap();
+        assert!(manager.is_handle_valid(handle));
+        assert_ne!(manager.get_by_handle(handle).unwrap(), &None);
+
+        let compressed = manager.load("localization.csv").unwrap();
+        assert_ne!(manager.get_by_handle(compressed).unwrap(), &None);
+
+        manager.remove("test_resources/index.csv").unwrap();
+        assert!(!manager.is_handle_valid(handle));
+        assert!(matches!(
+            manager.get_by_handle(handle),
+            Err(crate::Error::InvalidHandle(_))
+        ));
+
+        // The slot is reused, but the old handle stay invalid
+        let new_handle = manager.load("index.csv").unwrap();
+        assert_eq!(new_handle.index(), handle.index());
+        assert!(!manager.is_handle_valid(handle));
+        assert!(manager.is_handle_valid(new_handle));
+    }
+
+    #[test]
+    pub fn typed_loaders() {
+        let mut index = crate::index::Index::new("./", "____________").unwrap();
+        index.set_csv_separator('/');
+        index.add_from_file("test_resources/index.csv").unwrap();
+
+        let dc = crate::decompression_manager::DecompressionManager::default();
+
+        let mut manager = crate::manager::AssetsManager::new(index, dc);
+        manager.load("deps.json").unwrap();
+        let deps = manager
+            .get_typed::<json::JsonValue>("deps.json")
+            .unwrap()
+            .unwrap();
+        assert!(deps["dependencies"].has_key("index.csv"));
+
+        assert!(matches!(
+            manager.get_typed::<pro_csv::CSV>("deps.json"),
+            Err(crate::Error::Unsupported(_))
+        ));
+
+        manager.load("localization.csv").unwrap();
+        let csv = manager
+            .get_typed::<pro_csv::CSV>("localization.csv")
+            .unwrap();
+        assert!(csv.is_some());
+
+        manager
+            .set_data("deps.json", b"{\"dependencies\": {}}".to_vec())
+            .unwrap();
+        let deps = manager
+            .get_typed::<json::JsonValue>("deps.json")
+            .unwrap()
+            .unwrap();
+        assert!(!deps["dependencies"].has_key("index.csv"));
+    }
+
+    #[test]
+    pub fn background_loading() {
+        let mut index = crate::index::Index::new("./", "____________").unwrap();
+        index.set_csv_separator('/');
+        index.add_from_file("test_resources/index.csv").unwrap();
+
+        let dc = crate::decompression_manager::DecompressionManager::default();
+
+        let mut manager = crate::manager::AssetsManager::new(index, dc);
+        let disk = manager.load_async("index.csv").unwrap();
+        let archive = manager.load_async("localization.csv").unwrap();
+        let missing = manager.load_async("other.toml").unwrap();
+
+        let mut completed = Vec::new();
+        while completed.len() < 3 {
+            completed.append(&mut manager.drain_completed());
+            std::thread::yield_now();
+        }
+        assert_eq!(manager.pending_loads(), 0);
+        assert!(manager.poll(disk).is_none());
+
+        for (ticket, result) in completed {
+            if ticket == missing {
+                assert!(result.is_err());
+            } else {
+                assert!(ticket == disk || ticket == archive);
+                let handle = result.unwrap();
+                assert_ne!(manager.get_by_handle(handle).unwrap(), &None);
+            }
+        }
+        assert_ne!(manager.get("localization.csv").unwrap(), None);
+
+        // A loading finishing after the file is loaded and modified does not replace the changes
+        let ticket = manager.load_async("redirect.json").unwrap();
+        let handle = manager.load("redirect.json").unwrap();
+        manager
+            .set_data("redirect.json", b"modified".to_vec())
+            .unwrap();
+        let result = loop {
+            if let Some(result) = manager.poll(ticket) {
+                break result;
+            }
+            std::thread::yield_now();
+        };
+        assert_eq!(result.unwrap(), handle);
+        assert_eq!(manager.get("redirect.json").unwrap().unwrap(), b"modified");
+    }
+
+    #[test]
+    pub fn memory_budget() {
+        let mut index = crate::index::Index::new("./", "____________").unwrap();
+        index.set_csv_separator('/');
+        index.add_from_file("test_resources/index.csv").unwrap();
+
+        let mut dc = crate::decompression_manager::DecompressionManager::default();
+        dc.set_cache_location(&std::env::temp_dir().to_string_lossy());
+
+        let mut manager = crate::manager::AssetsManager::new(index, dc);
+        let index_csv = manager.load("index.csv").unwrap();
+        manager.load("localization.csv").unwrap();
+        manager.load("redirect.json").unwrap();
+
+        let index_size = manager.get("index.csv").unwrap().unwrap().len();
+        assert!(manager.memory_usage() > index_size);
+
+        // Only the last used file can stay in memory
+        manager.set_memory_budget(Some(index_size)).unwrap();
+        assert_eq!(manager.memory_usage(), index_size);
+        assert!(manager
+            .get_file_by_handle(index_csv)
+            .unwrap()
+            .data
+            .is_some());
+
+        // Evicted files are reloaded when used
+        assert_ne!(manager.get("localization.csv").unwrap(), None);
+        assert!(manager
+            .get_file_by_handle(index_csv)
+            .unwrap()
+            .data
+            .is_none());
+        assert_ne!(manager.get("redirect.json").unwrap(), None);
+        assert!(manager.memory_usage() <= index_size);
+
+        manager.set_memory_budget(None).unwrap();
+        assert_ne!(manager.get("index.csv").unwrap(), None);
+        assert_ne!(manager.get("localization.csv").unwrap(), None);
+        assert!(manager.memory_usage() > index_size);
+
+        // The modified data is counted
+        let usage = manager.memory_usage();
+        manager
+            .set_data("index.csv", vec![0; index_size + 10])
+            .unwrap();
+        assert_eq!(manager.memory_usage(), usage + 10);
+    }
+
+    #[test]
+    pub fn memory_budget_archive_entries() {
+        let dir = TestDir::new("memory_budget_archive_entries");
+        let root = dir.path();
+        write_zip(
+            &format!("{}/levels.zip", root),
+            &[("lv/one.txt", b"one"), ("lv/two.txt", b"two")],
+        );
+
+        // The entries in directories are spilled in the default cache location
+        let mut manager = crate::manager::AssetsManager::default();
+        manager.set_memory_budget(Some(3)).unwrap();
+        let one = manager
+            .load(&format!("{}/levels.zip/lv/one.txt", root))
+            .unwrap();
+        let two = manager
+            .load(&format!("{}/levels.zip/lv/two.txt", root))
+            .unwrap();
+        assert_eq!(manager.memory_usage(), 3);
+        assert!(manager.get_file_by_handle(one).unwrap().evicted);
+
+        assert_eq!(manager.get_by_handle(one).unwrap().clone().unwrap(), b"one");
+        assert!(manager.get_file_by_handle(two).unwrap().evicted);
+        assert_eq!(manager.get_by_handle(two).unwrap().clone().unwrap(), b"two");
+    }
+
+    #[test]
+    pub fn reference_counting() {
+        let mut index = crate::index::Index::new("./", "____________").unwrap();
+        index.set_csv_separator('/');
+        index.add_from_file("test_resources/index.csv").unwrap();
+
+        let dc = crate::decompression_manager::DecompressionManager::default();
+
+        let mut manager = crate::manager::AssetsManager::new(index, dc);
+        let first = manager.acquire("index.csv").unwrap();
+        let second = manager.acquire("index.csv").unwrap();
+        assert_eq!(first, second);
+        assert_eq!(manager.load("index.csv").unwrap(), first);
+        assert_eq!(manager.ref_count(first), 2);
+
+        assert!(matches!(
+            manager.remove("test_resources/index.csv"),
+            Err(crate::Error::InUse(_))
+        ));
+
+        assert!(!manager.release("index.csv").unwrap());
+        assert_ne!(manager.get("index.csv").unwrap(), None);
+
+        assert!(manager.release_by_handle(first).unwrap());
+        assert_eq!(manager.get("index.csv").unwrap(), None);
+
+        // The handle stay valid, and the file can be acquired again
+        assert_eq!(manager.acquire("index.csv").unwrap(), first);
+        assert_ne!(manager.get("index.csv").unwrap(), None);
+        assert!(manager.release_by_handle(first).unwrap());
+        manager.remove("test_resources/index.csv").unwrap();
+        assert!(!manager.is_handle_valid(first));
+    }
+
+    /// A directory created in the temporary directory for a test, and removed when dropped
+    struct TestDir {
+        path: String,
+    }
+
+    impl TestDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "fast-assets-test-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = std::fs::remove_dir_all(&path);
+            std::fs::create_dir_all(&path).unwrap();
+            Self {
+                path: path.to_string_lossy().to_string(),
+            }
+        }
+
+        fn path(&self) -> &str {
+            &self.path
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.path);
+        }
+    }
+
+    fn write_zip(path: &str, entries: &[(&str, &[u8])]) {
+        use std::io::Write;
+
+        let file = std::fs::File::create(path).unwrap();
+        let mut zip = zip::ZipWriter::new(file);
+        for (name, data) in entries {
+            zip.start_file(*name, zip::write::FileOptions::default())
+                .unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    pub fn hot_reload() {
+        use crate::watcher::ChangeKind;
+
+        let dir = TestDir::new("hot_reload");
+        let root = dir.path();
+        std::fs::write(format!("{}/config.json", root), b"{}").unwrap();
+        write_zip(&format!("{}/pack.zip", root), &[("entry.txt", b"old")]);
+
+        let mut index = crate::index::Index::new(root, "").unwrap();
+        index.search().unwrap();
+        let dc = crate::decompression_manager::DecompressionManager::default();
+        let mut manager = crate::manager::AssetsManager::new(index, dc);
+        manager.load("config.json").unwrap();
+        manager
+            .load(&format!("{}/pack.zip/entry.txt", root))
+            .unwrap();
+        manager.enable_hot_reload().unwrap();
+
+        std::fs::write(format!("{}/config.json", root), b"{\"a\": 1}").unwrap();
+        write_zip(&format!("{}/pack.zip", root), &[("entry.txt", b"new data")]);
+        std::fs::write(format!("{}/added.txt", root), b"").unwrap();
+        manager.check_changes().unwrap();
+
+        let changes = manager.changed_assets();
+        let kind_of = |name: &str| {
+            changes
+                .iter()
+                .find(|change| change.path.ends_with(name))
+                .map(|change| change.kind)
+        };
+        assert_eq!(kind_of("config.json"), Some(ChangeKind::Modified));
+        assert_eq!(kind_of("pack.zip"), Some(ChangeKind::Modified));
+        assert_eq!(kind_of("entry.txt"), Some(ChangeKind::Modified));
+        assert_eq!(kind_of("added.txt"), Some(ChangeKind::Added));
+        assert!(manager.changed_assets().is_empty());
+
+        assert_eq!(manager.get("config.json").unwrap().unwrap(), b"{\"a\": 1}");
+        assert_eq!(manager.get("entry.txt").unwrap().unwrap(), b"new data");
+        assert!(manager.index.have_file("added.txt"));
+
+        std::fs::remove_file(format!("{}/added.txt", root)).unwrap();
+        manager.check_changes().unwrap();
+        assert_eq!(manager.changed_assets()[0].kind, ChangeKind::Removed);
+        assert!(!manager.index.have_file("added.txt"));
+    }
+
+    #[test]
+    pub fn hot_reload_archive_entries() {
+        use crate::watcher::ChangeKind;
+
+        let dir = TestDir::new("hot_reload_archive_entries");
+        let root = dir.path();
+        std::fs::create_dir_all(format!("{}/packs", root)).unwrap();
+        write_zip(&format!("{}/packs/base.zip", root), &[("a.txt", b"a")]);
+
+        let mut index = crate::index::Index::new(root, ".*").unwrap();
+        index.set_archive_indexing(true);
+        index.search().unwrap();
+        let mut manager = crate::manager::AssetsManager::new(index, Default::default());
+        manager.enable_hot_reload().unwrap();
+        manager.check_changes().unwrap();
+        assert!(manager.changed_assets().is_empty());
+
+        // The entries of the new archives are indexed
+        write_zip(&format!("{}/packs/dlc.zip", root), &[("b.txt", b"b")]);
+        manager.check_changes().unwrap();
+        let changes = manager.changed_assets();
+        assert!(changes.iter().any(
+            |change| change.path.ends_with("dlc.zip/b.txt") && change.kind == ChangeKind::Added
+        ));
+        manager.load("b.txt").unwrap();
+        assert_eq!(manager.get("b.txt").unwrap().unwrap(), b"b");
+
+        // The entries removed from a modified archive are removed from the index
+        write_zip(&format!("{}/packs/base.zip", root), &[("c.txt", b"c")]);
+        manager.check_changes().unwrap();
+        assert!(!manager.index.have_file("a.txt"));
+        assert!(manager.index.have_file("c.txt"));
+
+        std::fs::remove_file(format!("{}/packs/dlc.zip", root)).unwrap();
+        manager.check_changes().unwrap();
+        assert!(!manager.index.have_file("b.txt"));
+        assert!(manager.index.have_file("c.txt"));
+    }
+
+    #[test]
+    pub fn backends() {
+        use crate::backend::{Backend, CombinedBackend, MemoryBackend, ZipBackend};
+        use std::path::Path;
+        use std::sync::Arc;
+
+        let memory = Arc::new(MemoryBackend::new());
+        memory
+            .write(Path::new("data/config.json"), b"{\"a\": 0}")
+            .unwrap();
+
+        let dir = TestDir::new("backends");
+        let archive = format!("{}/backend.zip", dir.path());
+        write_zip(&archive, &[("textures/stone.txt", b"stone")]);
+        let zip = Arc::new(ZipBackend::open(&archive).unwrap());
+        assert!(zip.write(Path::new("new.txt"), b"").is_err());
+
+        let combined = CombinedBackend::new(vec![memory.clone(), zip]);
+        let mut index = crate::index::Index::new("./", ".*").unwrap();
+        index.set_backend(Arc::new(combined));
+        index.search().unwrap();
+        assert!(index.have_file("config.json"));
+        assert!(index.have_file("stone.txt"));
+
+        let mut manager = crate::manager::AssetsManager::new(
+            index,
+            crate::decompression_manager::DecompressionManager::default(),
+        );
+        manager.load("stone.txt").unwrap();
+        assert_eq!(manager.get("stone.txt").unwrap().unwrap(), b"stone");
+
+        manager.load("config.json").unwrap();
+        manager
+            .set_data("config.json", b"{\"a\": 1}".to_vec())
+            .unwrap();
+        manager.save("config.json").unwrap();
+        assert_eq!(
+            memory.read(Path::new("data/config.json")).unwrap(),
+            b"{\"a\": 1}"
+        );
+
+        // New files are written to the first backend accepting them
+        manager
+            .copy_file("stone.txt", "data/stone_copy.txt")
+            .unwrap();
+        assert_eq!(
+            memory.read(Path::new("data/stone_copy.txt")).unwrap(),
+            b"stone"
+        );
+        manager
+            .move_file("stone_copy.txt", "data/moved.txt")
+            .unwrap();
+        assert!(!memory.exists(Path::new("data/stone_copy.txt")));
+        assert!(manager.index.have_file("moved.txt"));
+        assert!(!Path::new("data").exists());
+    }
+
+    #[test]
+    pub fn mounts() {
+        use crate::backend::{Backend, FsBackend};
+        use std::path::Path;
+
+        let dir = TestDir::new("mounts");
+        let root = dir.path();
+        std::fs::create_dir_all(format!("{}/base/textures", root)).unwrap();
+        std::fs::create_dir_all(format!("{}/mod", root)).unwrap();
+        std::fs::write(format!("{}/base/textures/stone.txt", root), b"base").unwrap();
+        std::fs::write(format!("{}/base/intro.txt", root), b"intro").unwrap();
+        write_zip(
+            &format!("{}/dlc.zip", root),
+            &[("textures/stone.txt", b"dlc"), ("dlc.txt", b"new")],
+        );
+
+        let mut manager = crate::manager::AssetsManager::new(
+            crate::index::Index::new("", ".*").unwrap(),
+            crate::decompression_manager::DecompressionManager::default(),
+        );
+        manager
+            .mount_dir("base", &format!("{}/base", root), 0)
+            .unwrap();
+        manager.load("stone.txt").unwrap();
+        assert_eq!(manager.get("stone.txt").unwrap().unwrap(), b"base");
+        assert_eq!(manager.layer_of("intro.txt"), Some(String::from("base")));
+        assert!(!manager.have_file("dlc.txt"));
+
+        manager
+            .mount_zip("dlc", &format!("{}/dlc.zip", root), 10)
+            .unwrap();
+        assert_eq!(manager.layer_of("stone.txt"), Some(String::from("dlc")));
+        assert_eq!(manager.get("stone.txt").unwrap().unwrap(), b"dlc");
+        assert!(manager.have_file("dlc.txt"));
+
+        // Modified files are written to the highest priority layer accepting them
+        manager
+            .mount_dir("mod", &format!("{}/mod", root), 5)
+            .unwrap();
+        manager.load("intro.txt").unwrap();
+        manager.set_data("intro.txt", b"modded".to_vec()).unwrap();
+        manager.save("intro.txt").unwrap();
+        assert_eq!(manager.layer_of("intro.txt"), Some(String::from("mod")));
+        assert_eq!(
+            std::fs::read(format!("{}/base/intro.txt", root)).unwrap(),
+            b"intro"
+        );
+
+        // Only the writable layer with the highest priority is modified by a removal
+        manager.remove_file("intro.txt").unwrap();
+        assert_eq!(manager.layer_of("intro.txt"), Some(String::from("base")));
+        assert!(manager.remove_file("intro.txt").is_err());
+        assert!(manager.have_file("intro.txt"));
+        assert!(Path::new(&format!("{}/base/intro.txt", root)).exists());
+
+        let names: Vec<String> = manager.mounts().into_iter().map(|m| m.name).collect();
+        assert_eq!(names, vec!["dlc", "mod", "base"]);
+
+        assert!(manager.unmount("dlc").unwrap());
+        assert!(!manager.unmount("dlc").unwrap());
+        assert_eq!(manager.get("stone.txt").unwrap().unwrap(), b"base");
+        assert!(!manager.have_file("dlc.txt"));
+
+        // The paths leaving the root of a directory are rejected
+        let backend = FsBackend::new(&format!("{}/base", root));
+        assert!(backend.read(Path::new("textures/stone.txt")).is_ok());
+        assert!(backend.read(Path::new("../base/intro.txt")).is_err());
+        let absolute = format!("{}/base/intro.txt", root);
+        assert!(backend.read(Path::new(&absolute)).is_err());
+    }
+
+    #[test]
+    pub fn corrupt_index_file() {
+        use crate::index_file::{IndexEntry, IndexFile};
+
+        let index_file = IndexFile {
+            root: PathBuf::from("assets"),
+            entries: vec![IndexEntry {
+                path: PathBuf::from("assets/a.txt"),
+                len: 1,
+                modified: Some(std::time::UNIX_EPOCH),
+                hash: 0,
+            }],
+            directories: Vec::new(),
+        };
+        let bytes = index_file.to_bytes();
+        assert_eq!(IndexFile::from_bytes(&bytes).unwrap(), index_file);
+
+        // Every truncation is an error, never a panic
+        for len in 0..bytes.len() {
+            assert!(matches!(
+                IndexFile::from_bytes(&bytes[..len]),
+                Err(crate::Error::Parse(_))
+            ));
+        }
+
+        // The time of the entry is after the path, the length and the flag
+        let time = 4 + 4 + (4 + 6) + 8 + (4 + 12) + 8 + 1;
+        let mut overflow = bytes.clone();
+        overflow[time..time + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(matches!(
+            IndexFile::from_bytes(&overflow),
+            Err(crate::Error::Parse(_))
+        ));
+        let mut nanos = bytes.clone();
+        nanos[time + 8..time + 12].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            IndexFile::from_bytes(&nanos),
+            Err(crate::Error::Parse(_))
+        ));
+    }
+
+    #[test]
+    pub fn index_file() {
+        use crate::watcher::ChangeKind;
+
+        let dir = TestDir::new("index_file");
+        let root = &format!("{}/assets", dir.path());
+        let index_file = &format!("{}/index.bin", dir.path());
+        std::fs::create_dir_all(format!("{}/textures", root)).unwrap();
+        std::fs::create_dir_all(format!("{}/sounds", root)).unwrap();
+        std::fs::write(format!("{}/config.json", root), b"{}").unwrap();
+        std::fs::write(format!("{}/textures/stone.txt", root), b"stone").unwrap();
+        std::fs::write(format!("{}/sounds/step.txt", root), b"step").unwrap();
+
+        let mut index = crate::index::Index::new(root, ".*").unwrap();
+        index.search().unwrap();
+        index.save_index_file(index_file).unwrap();
+
+        let mut loaded = crate::index::Index::new(root, ".*").unwrap();
+        loaded.load_index_file(index_file).unwrap();
+        assert_eq!(loaded.files().len(), 3);
+        let stone = loaded
+            .file_info(&format!("{}/textures/stone.txt", root))
+            .unwrap();
+        assert_eq!(stone.len, 5);
+        assert_eq!(stone.hash, crate::index_file::hash_content(b"stone"));
+
+        let mut other_root = crate::index::Index::new("./", ".*").unwrap();
+        assert!(other_root.load_index_file(index_file).is_err());
+        std::fs::write(index_file, b"FAIX").unwrap();
+        assert!(matches!(
+            other_root.load_index_file(index_file),
+            Err(crate::Error::Parse(_))
+        ));
+        std::fs::remove_file(index_file).unwrap();
+
+        assert!(loaded.rescan().unwrap().is_empty());
+
+        std::fs::write(format!("{}/textures/dirt.txt", root), b"dirt").unwrap();
+        std::fs::remove_file(format!("{}/config.json", root)).unwrap();
+        std::fs::remove_dir_all(format!("{}/sounds", root)).unwrap();
+        let changes = loaded.rescan().unwrap();
+        let kinds: Vec<(String, ChangeKind)> = changes
+            .iter()
+            .map(|change| {
+                (
+                    change
+                        .path
+                        .file_name()
+                        .unwrap()
+                        .to_string_lossy()
+                        .to_string(),
+                    change.kind,
+                )
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (String::from("config.json"), ChangeKind::Removed),
+                (String::from("step.txt"), ChangeKind::Removed),
+                (String::from("dirt.txt"), ChangeKind::Added),
+            ]
+        );
+        assert!(loaded.have_file("dirt.txt"));
+        assert!(!loaded.have_file("step.txt"));
+        assert!(loaded.rescan().unwrap().is_empty());
+    }
+
+    #[test]
+    pub fn index_lookup() {
+        let mut index = crate::index::Index::new("./", ".*").unwrap();
+        index.add_files(vec![
+            PathBuf::from("base/config.json"),
+            PathBuf::from("mods/config.json"),
+            PathBuf::from("base/stone.png"),
+        ]);
+        index.add_file(PathBuf::from("base/stone.png"));
+        assert_eq!(index.files().len(), 3);
+
+        // The full path is used first, then the file name if it is not ambiguous
+        assert!(matches!(
+            index.get_path("config.json"),
+            Err(crate::Error::Ambiguous(_))
+        ));
+        assert_eq!(
+            index.get_path("base/config.json").ok(),
+            Some(String::from("base/config.json"))
+        );
+
+        assert!(!index.remove_indexed_file("config.json"));
+        assert!(index.remove_indexed_file("mods/config.json"));
+        assert_eq!(
+            index.get_path("config.json").ok(),
+            Some(String::from("base/config.json"))
+        );
+        assert!(index.have_file("base/stone.png"));
+        assert!(!index.have_file("mods/config.json"));
+
+        index.clear();
+        assert!(!index.have_file("stone.png"));
+        assert_eq!(index.get_path("stone.png").ok(), None);
+    }
+
+    #[test]
+    pub fn resolution_policy() {
+        use crate::index::ResolutionPolicy;
+
+        let mut index = crate::index::Index::new("./", ".*").unwrap();
+        index.add_files(vec![
+            PathBuf::from("mods/extra/config.json"),
+            PathBuf::from("base/config.json"),
+            PathBuf::from("mods/config.json"),
+            PathBuf::from("base/stone.png"),
+        ]);
+
+        // Ambiguous by default
+        assert!(matches!(
+            index.get_path("config.json"),
+            Err(crate::Error::Ambiguous(_))
+        ));
+        index.set_resolution_policy(ResolutionPolicy::FirstIndexed);
+        assert_eq!(
+            index.get_path("config.json").unwrap(),
+            "mods/extra/config.json"
+        );
+        index.set_resolution_policy(ResolutionPolicy::ShortestPath);
+        assert_eq!(index.get_path("config.json").unwrap(), "base/config.json");
+        index.set_resolution_policy(ResolutionPolicy::Error);
+        match index.get_path("config.json") {
+            Err(crate::Error::Ambiguous(files)) => assert_eq!(files.len(), 3),
+            other => panic!("Expected an ambiguous result, got {:?}", other),
+        }
+        assert_eq!(index.get_path("stone.png").unwrap(), "base/stone.png");
+        assert!(matches!(
+            index.get_path("missing.png"),
+            Err(crate::Error::NotIndexed(_))
+        ));
+
+        let duplicates = index.find_duplicates();
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].0, "config.json");
+        assert_eq!(duplicates[0].1[0], PathBuf::from("mods/extra/config.json"));
+    }
+
+    #[test]
+    pub fn archive_indexing() {
+        let dir = TestDir::new("archive_indexing");
+        let root = dir.path();
+        write_zip(
+            &format!("{}/localization.zip", root),
+            &[("localization.csv", b"en;fr"), ("fonts/main.ttf", b"font")],
+        );
+
+        let mut index = crate::index::Index::new(root, ".*").unwrap();
+        index.search().unwrap();
+        assert!(!index.have_file("localization.csv"));
+
+        let mut index = crate::index::Index::new(root, "\\.(csv|zip)$").unwrap();
+        index.set_archive_indexing(true);
+        index.search().unwrap();
+        assert_eq!(
+            index.get_path("localization.csv").unwrap(),
+            format!("{}/localization.zip/localization.csv", root)
+        );
+        assert!(index.have_file("localization.zip"));
+        assert!(!index.have_file("main.ttf"));
+
+        let mut manager = crate::manager::AssetsManager::new(
+            index,
+            crate::decompression_manager::DecompressionManager::default(),
+        );
+        manager.load("localization.csv").unwrap();
+        assert_eq!(manager.get("localization.csv").unwrap().unwrap(), b"en;fr");
+    }
+
+    #[test]
+    pub fn nested_archives() {
+        let dir = TestDir::new("nested_archives");
+        let root = dir.path();
+        std::fs::create_dir_all(format!("{}/packs", root)).unwrap();
+        write_zip(
+            &format!("{}/textures.zip", root),
+            &[("grass.png", b"grass")],
+        );
+        let textures = std::fs::read(format!("{}/textures.zip", root)).unwrap();
+        write_zip(
+            &format!("{}/packs/base.zip", root),
+            &[("textures.zip", &textures), ("readme.txt", b"base")],
+        );
+
+        let mut manager = crate::manager::AssetsManager::default();
+        let path = format!("{}/packs/base.zip/textures.zip/grass.png", root);
+        let handle = manager.load(&path).unwrap();
+        assert_eq!(
+            manager.get_by_handle(handle).unwrap().clone().unwrap(),
+            b"grass"
+        );
+        let file = manager.get_file_by_handle(handle).unwrap();
+        assert_eq!(
+            file.archive,
+            Some(PathBuf::from(format!(
+                "{}/packs/base.zip/textures.zip",
+                root
+            )))
+        );
+        // The data of the inner archive is kept
+        assert!(manager.memory_usage() >= textures.len() + 5);
+
+        // The inner archive is evicted first, and read again when needed
+        manager.set_memory_budget(Some(5)).unwrap();
+        assert_eq!(manager.memory_usage(), 5);
+        assert_eq!(
+            manager.get_by_handle(handle).unwrap().clone().unwrap(),
+            b"grass"
+        );
+        manager.set_memory_budget(None).unwrap();
+
+        // The workers read the nested archives without the cache
+        let mut other = crate::manager::AssetsManager::default();
+        let ticket = other.load_async(&path).unwrap();
+        while !other.is_ready(ticket) {
+            std::thread::yield_now();
+        }
+        let other_handle = other.poll(ticket).unwrap().unwrap();
+        assert_eq!(
+            other.get_by_handle(other_handle).unwrap().clone().unwrap(),
+            b"grass"
+        );
+
+        assert!(manager
+            .load(&format!("{}/packs/base.zip/textures.zip/missing.png", root))
+            .is_err());
+    }
+
+    #[test]
+    pub fn packer() {
+        let dir = TestDir::new("packer");
+        let root = dir.path();
+        std::fs::create_dir_all(format!("{}/assets/textures", root)).unwrap();
+        std::fs::write(format!("{}/assets/textures/stone.png", root), b"stone").unwrap();
+        std::fs::write(format!("{}/assets/intro.txt", root), b"intro").unwrap();
+        std::fs::write(format!("{}/assets/notes.md", root), b"notes").unwrap();
+
+        let mut index = crate::index::Index::new(&format!("{}/assets", root), ".*").unwrap();
+        index.search().unwrap();
+        let mut packer = crate::packer::Packer::from_regex(&index, r"\.(png|txt)$").unwrap();
+        packer.set_default_compression(zip::CompressionMethod::Stored);
+        packer.set_compression(".TXT", zip::CompressionMethod::Deflated);
+        assert_eq!(packer.files().len(), 2);
+
+        let bundle = format!("{}/bundle.zip", root);
+        let index_path = format!("{}/bundle.bin", root);
+        let packed = packer.pack(&bundle, &index_path).unwrap();
+        assert_eq!(packed.entries.len(), 2);
+
+        let mut zip = zip::ZipArchive::new(std::fs::File::open(&bundle).unwrap()).unwrap();
+        assert_eq!(
+            zip.by_name("textures/stone.png").unwrap().compression(),
+            zip::CompressionMethod::Stored
+        );
+        assert_eq!(
+            zip.by_name("intro.txt").unwrap().compression(),
+            zip::CompressionMethod::Deflated
+        );
+        assert!(zip.by_name("notes.md").is_err());
+
+        // The bundle is loaded using the index file, without searching
+        let mut index = crate::index::Index::new(root, ".*").unwrap();
+        index.load_index_file(&index_path).unwrap();
+        let stone = index
+            .file_info(&format!("{}/textures/stone.png", bundle))
+            .unwrap();
+        assert_eq!(stone.hash, crate::index_file::hash_content(b"stone"));
+        let mut manager = crate::manager::AssetsManager::new(index, Default::default());
+        let handle = manager.load("stone.png").unwrap();
+        assert_eq!(
+            manager.get_by_handle(handle).unwrap().clone().unwrap(),
+            b"stone"
+        );
+    }
+
+    #[test]
+    pub fn archive_write_back() {
+        use crate::archive::ArchiveFormat;
+        use std::io::{Read, Write};
+
+        let dir = TestDir::new("archive_write_back");
+        let root = dir.path();
+        let archive = format!("{}/data.zip", root);
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&archive).unwrap());
+        let stored =
+            zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        zip.start_file("save.json", stored).unwrap();
+        zip.write_all(b"{}").unwrap();
+        zip.start_file("levels/one.txt", zip::write::FileOptions::default())
+            .unwrap();
+        zip.write_all(b"level one").unwrap();
+        zip.finish().unwrap();
+        let inner = std::fs::read(&archive).unwrap();
+        write_zip(
+            &format!("{}/pack.zip", root),
+            &[("data.zip", &inner), ("readme.txt", b"pack")],
+        );
+
+        let mut manager = crate::manager::AssetsManager::default();
+        manager.load(&format!("{}/save.json", archive)).unwrap();
+        manager
+            .set_data("save.json", b"{\"level\": 2}".to_vec())
+            .unwrap();
+        manager.save("save.json").unwrap();
+
+        let mut zip = zip::ZipArchive::new(std::fs::File::open(&archive).unwrap()).unwrap();
+        let mut content = String::new();
+        let mut file = zip.by_name("save.json").unwrap();
+        assert_eq!(file.compression(), zip::CompressionMethod::Stored);
+        file.read_to_string(&mut content).unwrap();
+        drop(file);
+        assert_eq!(content, "{\"level\": 2}");
+        content.clear();
+        zip.by_name("levels/one.txt")
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "level one");
+        // The archive is replaced by a rename, no temporary file is left
+        assert_eq!(std::fs::read_dir(root).unwrap().count(), 2);
+
+        // The files of a nested archive are written back in all the parents
+        let mut manager = crate::manager::AssetsManager::default();
+        let pack = format!("{}/pack.zip", root);
+        manager
+            .load(&format!("{}/data.zip/levels/one.txt", pack))
+            .unwrap();
+        manager
+            .set_data("levels/one.txt", b"level two".to_vec())
+            .unwrap();
+        manager.save("levels/one.txt").unwrap();
+        let data = std::fs::read(&pack).unwrap();
+        let inner = crate::archive::read_entry(ArchiveFormat::Zip, &data, "data.zip").unwrap();
+        assert_eq!(
+            crate::archive::read_entry(ArchiveFormat::Zip, &inner, "levels/one.txt").unwrap(),
+            b"level two"
+        );
+        assert_eq!(
+            crate::archive::read_entry(ArchiveFormat::Zip, &data, "readme.txt").unwrap(),
+            b"pack"
+        );
+
+        // The same entry loaded from two archives must be saved using its full path
+        manager.load(&format!("{}/save.json", archive)).unwrap();
+        manager
+            .load(&format!("{}/data.zip/save.json", pack))
+            .unwrap();
+        assert!(matches!(
+            manager.save("save.json"),
+            Err(crate::Error::Ambiguous(files)) if files.len() == 2
+        ));
+        manager.save(&format!("{}/save.json", archive)).unwrap();
+
+        // A file from an archive cannot be saved alone, the manager must rebuild the archive
+        let mut file = crate::manager::File {
+            from_archive: true,
+            path: PathBuf::from("save.json"),
+            data: Some(Vec::new()),
+            ..Default::default()
+        };
+        assert!(file.save().is_err());
+    }
+
+    #[test]
+    pub fn same_entry_in_archives() {
+        let dir = TestDir::new("same_entry_in_archives");
+        let root = dir.path();
+        write_zip(&format!("{}/a.zip", root), &[("x.txt", b"AAA")]);
+        write_zip(&format!("{}/b.zip", root), &[("x.txt", b"BBB")]);
+
+        let mut manager = crate::manager::AssetsManager::default();
+        let a = manager.load(&format!("{}/a.zip/x.txt", root)).unwrap();
+        let b = manager.load(&format!("{}/b.zip/x.txt", root)).unwrap();
+        assert_ne!(a, b);
+        assert_eq!(manager.get_by_handle(a).unwrap().clone().unwrap(), b"AAA");
+        assert_eq!(manager.get_by_handle(b).unwrap().clone().unwrap(), b"BBB");
+        assert_eq!(
+            manager
+                .get(&format!("{}/b.zip/x.txt", root))
+                .unwrap()
+                .unwrap(),
+            b"BBB"
+        );
+        // The path of the entry alone does not tell which archive to use
+        assert!(matches!(
+            manager.get("x.txt"),
+            Err(crate::Error::Ambiguous(files)) if files.len() == 2
+        ));
+
+        manager.remove(&format!("{}/a.zip/x.txt", root)).unwrap();
+        assert!(!manager.is_handle_valid(a));
+        assert_eq!(manager.get("x.txt").unwrap().unwrap(), b"BBB");
+
+        // Releasing the last holder unloads the entry
+        let handle = manager.acquire(&format!("{}/b.zip/x.txt", root)).unwrap();
+        assert_eq!(handle, b);
+        assert!(manager.release_by_handle(b).unwrap());
+        assert_eq!(manager.memory_usage(), 0);
+        assert_eq!(manager.get_by_handle(b).unwrap(), &None);
+    }
+
+    #[cfg(all(feature = "tar-gz", feature = "tar-zst"))]
+    #[test]
+    pub fn tar_archives() {
+        use crate::archive::ArchiveFormat;
+        use std::path::Path;
+
+        let dir = TestDir::new("tar_archives");
+        let root = dir.path();
+
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(5);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "./maps/level.txt", &b"level"[..])
+            .unwrap();
+        let tarball = builder.into_inner().unwrap();
+
+        std::fs::write(format!("{}/maps.tar", root), &tarball).unwrap();
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        std::io::Write::write_all(&mut gz, &tarball).unwrap();
+        std::fs::write(format!("{}/maps.tar.gz", root), gz.finish().unwrap()).unwrap();
+        let zst = zstd::stream::encode_all(&tarball[..], 0).unwrap();
+        std::fs::write(format!("{}/maps.tar.zst", root), zst).unwrap();
+
+        assert_eq!(
+            ArchiveFormat::from_path(Path::new("maps.tar.gz")),
+            Some(ArchiveFormat::TarGz)
+        );
+        assert_eq!(
+            ArchiveFormat::from_path(Path::new("maps.tgz")),
+            Some(ArchiveFormat::TarGz)
+        );
+        assert_eq!(ArchiveFormat::from_path(Path::new("maps.gz")), None);
+
+        let mut manager = crate::manager::AssetsManager::default();
+        let mut handles = Vec::new();
+        for archive in ["maps.tar", "maps.tar.gz", "maps.tar.zst"] {
+            let path = format!("{}/{}/maps/level.txt", root, archive);
+            let handle = manager.load(&path).unwrap();
+            assert_eq!(
+                manager.get_by_handle(handle).unwrap().clone().unwrap(),
+                b"level"
+            );
+            assert!(!handles.contains(&handle));
+            handles.push(handle);
+        }
+
+        let mut index = crate::index::Index::new(root, ".*").unwrap();
+        index.set_archive_indexing(true);
+        index.search().unwrap();
+        assert_eq!(index.find_duplicates()[0].1.len(), 3);
+    }
+
+    #[cfg(all(feature = "gz", feature = "zst", feature = "lz4"))]
+    #[test]
+    pub fn compressed_files() {
+        use crate::codec::Codec;
+
+        let dir = TestDir::new("compressed_files");
+        let root = dir.path();
+        for codec in Codec::all() {
+            let path = format!("{}/level.{}.{}", root, codec.extension(), codec.extension());
+            std::fs::write(path, codec.compress(b"level data").unwrap()).unwrap();
+        }
+
+        let mut index = crate::index::Index::new(root, ".*").unwrap();
+        index.search().unwrap();
+        let mut manager = crate::manager::AssetsManager::new(
+            index,
+            crate::decompression_manager::DecompressionManager::default(),
+        );
+
+        for codec in Codec::all() {
+            let name = format!("level.{}", codec.extension());
+            let handle = manager.load(&name).unwrap();
+            let file = manager.get_file_by_handle(handle).unwrap();
+            assert_eq!(file.codec, Some(codec));
+            assert_eq!(file.data.as_deref(), Some(&b"level data"[..]));
+        }
+
+        // Saving compress the data again with the same codec
+        manager.set_data("level.zst", b"saved".to_vec()).unwrap();
+        manager.save("level.zst").unwrap();
+        let saved = std::fs::read(format!("{}/level.zst.zst", root)).unwrap();
+        assert_eq!(Codec::Zstd.decompress(&saved).unwrap(), b"saved");
+
+        // Full paths are also resolved
+        let handle = manager.load(&format!("{}/level.gz", root)).unwrap();
+        assert_eq!(
+            manager.get_file_by_handle(handle).unwrap().codec,
+            Some(Codec::Gzip)
+        );
+    }
 }
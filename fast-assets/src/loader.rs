@@ -0,0 +1,62 @@
+use crate::error::Result;
+use std::any::Any;
+use std::fmt::{Debug, Formatter};
+
+/// Decode the raw data of the files matching some extensions into a Rust type
+pub trait AssetLoader: Debug {
+    /// Return the extensions supported by the loader (without the dot)
+    fn extensions(&self) -> Vec<String>;
+
+    /// Decode the raw data of a file
+    fn load(&self, data: &[u8]) -> Result<Box<dyn Any>>;
+}
+
+/// The decoded value of a file, produced by an AssetLoader
+pub struct TypedData(pub Box<dyn Any>);
+
+impl Debug for TypedData {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TypedData(..)")
+    }
+}
+
+/// Decode JSON files into json::JsonValue
+#[derive(Debug, Default)]
+pub struct JsonLoader {}
+
+impl AssetLoader for JsonLoader {
+    fn extensions(&self) -> Vec<String> {
+        vec![String::from("json")]
+    }
+
+    fn load(&self, data: &[u8]) -> Result<Box<dyn Any>> {
+        let content = std::str::from_utf8(data)?;
+        Ok(Box::new(json::parse(content)?))
+    }
+}
+
+/// Decode CSV files into pro_csv::CSV
+#[derive(Debug)]
+pub struct CsvLoader {
+    pub separator: char,
+}
+
+impl Default for CsvLoader {
+    fn default() -> Self {
+        Self { separator: ';' }
+    }
+}
+
+impl AssetLoader for CsvLoader {
+    fn extensions(&self) -> Vec<String> {
+        vec![String::from("csv")]
+    }
+
+    fn load(&self, data: &[u8]) -> Result<Box<dyn Any>> {
+        let content = std::str::from_utf8(data)?;
+        let mut csv = pro_csv::CSV::default();
+        csv.set_sperator_char(self.separator);
+        csv.load(content);
+        Ok(Box::new(csv))
+    }
+}
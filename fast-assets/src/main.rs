@@ -0,0 +1,373 @@
+use fast_assets::archive::{self, ArchiveFormat};
+use fast_assets::backend::{Backend, FsBackend};
+use fast_assets::decompression_manager::DecompressionManager;
+use fast_assets::dependencie_manager::{DependencieManager, VersionMismatch};
+use fast_assets::index::Index;
+use fast_assets::index_file::IndexFile;
+use fast_assets::manager::AssetsManager;
+use fast_assets::packer::Packer;
+use fast_assets::{Error, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+const USAGE: &str = "Usage: fast-assets <command>
+
+Commands:
+  index build <root> [--filter <regex>] [--out <index.bin>]
+  index ls [--index <index.bin>]
+  index find <name> [--index <index.bin>]
+  pack <root> [--filter <regex>] [--out <bundle.zip>] [--index-out <bundle.bin>]
+  unpack <archive> [--out <directory>]
+  verify [--index <index.bin>] [--redirects <redirect.json>] [--deps <deps.json>] [--versions <versions.json>]
+  deps graph <deps.json> [--index <index.bin>] [--versions <versions.json>] [--format <text|dot|json>]
+  deps diff <deps.json> [--index <index.bin>] [--scan <regex>]
+
+The dependencies and versions files are read from the disk, the other files using the index.";
+
+/// The positional arguments and the options given as "--name value"
+struct Args {
+    positional: Vec<String>,
+    options: HashMap<String, String>,
+    /// "--help" or "-h" was given
+    help: bool,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self> {
+        let mut result = Self {
+            positional: Vec::new(),
+            options: HashMap::new(),
+            help: false,
+        };
+        while let Some(arg) = args.next() {
+            if arg == "--help" || arg == "-h" {
+                result.help = true;
+                continue;
+            }
+            match arg.strip_prefix("--") {
+                Some(name) => match args.next() {
+                    Some(value) => {
+                        result.options.insert(name.to_string(), value);
+                    }
+                    None => return Err(Error::Parse(format!("Missing value of \"--{}\"", name))),
+                },
+                None => result.positional.push(arg),
+            }
+        }
+        Ok(result)
+    }
+
+    fn positional(&self, position: usize, name: &str) -> Result<&str> {
+        self.positional
+            .get(position)
+            .map(String::as_str)
+            .ok_or_else(|| Error::Parse(format!("Missing argument <{}>", name)))
+    }
+
+    fn option<'a>(&'a self, name: &str, default: &'a str) -> &'a str {
+        self.options.get(name).map_or(default, String::as_str)
+    }
+}
+
+fn main() -> ExitCode {
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => return fail(err),
+    };
+
+    if args.help {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+
+    let command: Vec<&str> = args.positional.iter().map(String::as_str).collect();
+    let result = match command.as_slice() {
+        ["index", "build", ..] => index_build(&args),
+        ["index", "ls", ..] => index_ls(&args),
+        ["index", "find", ..] => index_find(&args),
+        ["pack", ..] => pack(&args),
+        ["unpack", ..] => unpack(&args),
+        ["verify", ..] => verify(&args),
+        ["deps", "graph", ..] => deps_graph(&args),
+        ["deps", "diff", ..] => deps_diff(&args),
+        _ => {
+            eprintln!("{}", USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(err) => fail(err),
+    }
+}
+
+fn fail(err: Error) -> ExitCode {
+    eprintln!("error: {}", err);
+    ExitCode::FAILURE
+}
+
+/// Open an index file created by "index build", using the root saved in it
+fn open_index(filename: &str) -> Result<Index> {
+    let index_file = IndexFile::from_bytes(&std::fs::read(filename)?)?;
+    let mut index = Index::new(&index_file.root.to_string_lossy(), ".*")?;
+    index.load_index_file(filename)?;
+    Ok(index)
+}
+
+fn index_build(args: &Args) -> Result<bool> {
+    let mut index = Index::new(args.positional(2, "root")?, args.option("filter", ".*"))?;
+    index.search()?;
+    let out = args.option("out", "index.bin");
+    index.save_index_file(out)?;
+    println!("{} files indexed in \"{}\"", index.files().len(), out);
+    Ok(true)
+}
+
+fn index_ls(args: &Args) -> Result<bool> {
+    let index_file = IndexFile::from_bytes(&std::fs::read(args.option("index", "index.bin"))?)?;
+    for entry in index_file.entries {
+        println!("{}\t{}", entry.path.display(), entry.len);
+    }
+    Ok(true)
+}
+
+fn index_find(args: &Args) -> Result<bool> {
+    let index = open_index(args.option("index", "index.bin"))?;
+    println!("{}", index.get_path(args.positional(2, "name")?)?);
+    Ok(true)
+}
+
+fn pack(args: &Args) -> Result<bool> {
+    let mut index = Index::new(args.positional(1, "root")?, ".*")?;
+    index.search()?;
+    let packer = Packer::from_regex(&index, args.option("filter", ".*"))?;
+    let out = args.option("out", "bundle.zip");
+    let index_out = args.option("index-out", "bundle.bin");
+    for file in [out, index_out] {
+        if let Some(parent) = Path::new(file).parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+    }
+    let packed = packer.pack(out, index_out)?;
+    println!(
+        "{} files packed in \"{}\", indexed in \"{}\"",
+        packed.entries.len(),
+        out,
+        index_out
+    );
+    Ok(true)
+}
+
+fn unpack(args: &Args) -> Result<bool> {
+    let archive = args.positional(1, "archive")?;
+    let format = ArchiveFormat::from_path(Path::new(archive))
+        .ok_or_else(|| Error::Unsupported(format!("\"{}\" is not a known archive", archive)))?;
+    let data = DecompressionManager::read_archive(&FsBackend::default(), archive)?;
+    let out = PathBuf::from(args.option("out", "."));
+    let entries = archive::read_entries(format, &data, None)?;
+    for (name, content) in entries.iter() {
+        let path = out.join(name);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, content)?;
+    }
+    println!("{} files extracted in \"{}\"", entries.len(), out.display());
+    Ok(true)
+}
+
+/// Return true if the file exists, or is an entry of an archive
+fn file_exists(backend: &dyn Backend, path: &Path) -> bool {
+    if backend.exists(path) {
+        return true;
+    }
+
+    path.ancestors().skip(1).any(|archive| {
+        ArchiveFormat::from_path(archive).is_some()
+            && match path.strip_prefix(archive) {
+                Ok(entry) => DecompressionManager::read_entry(
+                    backend,
+                    &archive.to_string_lossy(),
+                    &entry.to_string_lossy().replace('\\', "/"),
+                )
+                .is_ok(),
+                Err(_) => false,
+            }
+    })
+}
+
+fn verify(args: &Args) -> Result<bool> {
+    let mut index = open_index(args.option("index", "index.bin"))?;
+    let mut problems = 0;
+
+    let backend = index.backend();
+    for file in index.files() {
+        if !file_exists(backend.as_ref(), file) {
+            println!("missing file: {}", file.display());
+            problems += 1;
+        }
+    }
+
+    if let Some(redirects) = args.options.get("redirects") {
+        if let Err(err) = index.add_redirect_from_file(redirects) {
+            println!("invalid redirect: {}", err);
+            problems += 1;
+        }
+    }
+
+    if let Some(deps) = args.options.get("deps") {
+        // The dependencies are checked using the files on the disk, the index file can be outdated
+        index.clear();
+        index.search()?;
+        let mut manager = AssetsManager::new(index, DecompressionManager::default());
+        let mut dependencies = read_dependencies(deps, args.options.get("versions"))?;
+        dependencies.update(&mut manager);
+        for dep in dependencies.dependencies() {
+            for file in dep.missing_list.iter() {
+                println!("missing dependency: {} requires {}", dep.source, file);
+                problems += 1;
+            }
+            for mismatch in dep.version_mismatches.iter() {
+                println!(
+                    "version mismatch: {} requires {} {}, found {}",
+                    dep.source,
+                    mismatch.file,
+                    mismatch.required,
+                    found_version(mismatch)
+                );
+                problems += 1;
+            }
+        }
+        if let Some(cycle) = dependencies.find_cycle() {
+            println!("dependency cycle: {}", cycle.join(" -> "));
+            problems += 1;
+        }
+    }
+
+    if problems == 0 {
+        println!("ok");
+    } else {
+        println!("{} problems found", problems);
+    }
+    Ok(problems == 0)
+}
+
+/// Read the dependencies file and the version manifest from the disk,
+/// they do not need to be in the indexed directory
+fn read_dependencies(deps: &str, versions: Option<&String>) -> Result<DependencieManager> {
+    let mut dependencies = DependencieManager::new();
+    dependencies.load_from_bytes(&std::fs::read(deps)?)?;
+    if let Some(versions) = versions {
+        dependencies.load_versions_from_bytes(&std::fs::read(versions)?)?;
+    }
+    Ok(dependencies)
+}
+
+/// Load the dependencies file, and check it using the index
+fn open_dependencies(args: &Args) -> Result<(AssetsManager, DependencieManager)> {
+    let index = open_index(args.option("index", "index.bin"))?;
+    let mut manager = AssetsManager::new(index, DecompressionManager::default());
+    let mut dependencies = read_dependencies(
+        args.positional(2, "deps.json")?,
+        args.options.get("versions"),
+    )?;
+    dependencies.update(&mut manager);
+    Ok((manager, dependencies))
+}
+
+fn found_version(mismatch: &VersionMismatch) -> String {
+    match mismatch.found {
+        Some(version) => version.to_string(),
+        None => String::from("no version"),
+    }
+}
+
+fn deps_graph(args: &Args) -> Result<bool> {
+    let (manager, dependencies) = open_dependencies(args)?;
+    match args.option("format", "text") {
+        "dot" => {
+            print!("{}", dependencies.export_dot(&manager.index));
+            return Ok(true);
+        }
+        "json" => {
+            println!("{}", dependencies.export_json(&manager.index));
+            return Ok(true);
+        }
+        "text" => (),
+        format => return Err(Error::Parse(format!("Unknown format \"{}\"", format))),
+    }
+
+    for dep in dependencies.dependencies() {
+        println!("{}", dep.source);
+        for file in dep.files() {
+            match dep.missing_list.contains(file) {
+                true => println!("  -> {} (missing)", file),
+                false => println!("  -> {}", file),
+            }
+        }
+        // The required patterns without matching file
+        for pattern in dep.missing_list.iter() {
+            if !dep.deps.contains(pattern) {
+                println!("  -> {} (no match)", pattern);
+            }
+        }
+        for mismatch in dep.version_mismatches.iter() {
+            println!(
+                "  -> {} (requires {}, found {})",
+                mismatch.file,
+                mismatch.required,
+                found_version(mismatch)
+            );
+        }
+    }
+    match dependencies.find_cycle() {
+        Some(cycle) => {
+            println!("cycle: {}", cycle.join(" -> "));
+            Ok(false)
+        }
+        None => Ok(true),
+    }
+}
+
+/// Compare the dependencies declared and the files referenced by the content of the declared files,
+/// and of the indexed files matching --scan
+fn deps_diff(args: &Args) -> Result<bool> {
+    let (mut manager, mut dependencies) = open_dependencies(args)?;
+    let mut files: Vec<String> = dependencies
+        .dependencies()
+        .iter()
+        .map(|dep| dep.source.clone())
+        .collect();
+    if let Some(filter) = args.options.get("scan") {
+        for file in manager.get_files_matching_regex(filter)? {
+            // The declared files are already scanned using their declared name
+            if !files.iter().any(|source| file.ends_with(source)) {
+                files.push(file.to_string_lossy().to_string());
+            }
+        }
+    }
+
+    let mut same = true;
+    for file in files {
+        if let Err(err) = dependencies.scan(&mut manager, &file) {
+            println!("{}: {}", file, err);
+            continue;
+        }
+        let diff = dependencies.diff(&file);
+        if diff.is_empty() {
+            continue;
+        }
+        same = false;
+        println!("{}", file);
+        diff.undeclared
+            .iter()
+            .for_each(|dep| println!("  + {} (not declared)", dep));
+        diff.unused
+            .iter()
+            .for_each(|dep| println!("  - {} (not referenced)", dep));
+    }
+    Ok(same)
+}
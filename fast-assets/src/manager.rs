@@ -1,56 +1,108 @@
+use crate::archive::{self, ArchiveFormat};
+use crate::backend::{normalize, Backend, FsBackend, ZipBackend};
+use crate::background::{BackgroundLoader, LoadSource, LoadTicket};
+use crate::codec::Codec;
 use crate::decompression_manager::DecompressionManager;
+use crate::dependencie_manager::DependencieManager;
 use crate::downloader::Downloader;
+use crate::error::{Error, Result};
 use crate::extension::Extension;
+use crate::handle::{AssetHandle, SlotMap};
 use crate::index::Index;
-use std::io::{Read, Write};
-use std::path::PathBuf;
+use crate::loader::{AssetLoader, CsvLoader, JsonLoader, TypedData};
+use crate::mount::{Mount, OverlayBackend};
+use crate::watcher::{same_path, AssetChange, ChangeKind, Watcher};
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 #[derive(Default, Debug)]
 pub struct File {
     pub from_archive: bool,
+    /// The archive containing the file, if from_archive
+    pub archive: Option<PathBuf>,
     pub path: PathBuf,
     pub data: Option<Vec<u8>>,
     pub downloaded: bool,
+    /// The compression of the file in the backend, the data is kept decompressed
+    pub codec: Option<Codec>,
+    /// The data decoded by an AssetLoader, reset when the data change
+    pub typed: Option<TypedData>,
+    /// Value of the access counter of the manager when the file was last used
+    pub(crate) last_access: u64,
+    /// Unloaded to respect the memory budget, and must be reloaded on the next access
+    pub(crate) evicted: bool,
+    /// Modified since loaded or saved
+    pub(crate) dirty: bool,
+    /// Number of holders using acquire, the file can only be removed once all released it
+    pub(crate) ref_count: usize,
 }
 
 impl File {
-    pub fn load(&mut self) -> std::io::Result<()> {
-        if self.path.exists() {
-            let mut buffer = Vec::<u8>::new();
-            let mut file = std::fs::File::options()
-                .read(true)
-                .open(self.path.clone())?;
-            file.read_to_end(&mut buffer)?;
-            file.flush()?;
-            self.data = Some(buffer);
-            return Ok(());
+    pub fn load(&mut self) -> Result<()> {
+        self.load_from(&FsBackend::default())
+    }
+
+    /// Return the path identifying the file in the manager,
+    /// the entries of archives are prefixed by the path of their archive
+    pub fn full_path(&self) -> PathBuf {
+        match &self.archive {
+            Some(archive) => archive.join(&self.path),
+            None => self.path.clone(),
         }
-        Err(std::io::Error::new(
-            std::io::ErrorKind::NotFound,
-            format!("File \"{:?}\" not found", self.path),
-        ))
-    }
-
-    pub fn save(&mut self) -> std::io::Result<()> {
-        if !self.downloaded {
-            let mut file = std::fs::File::options()
-                .write(true)
-                .truncate(true)
-                .create(true)
-                .open(self.path.clone())?;
-            match &self.data {
-                Some(data) => {
-                    file.write_all(data.as_slice())?;
-                    file.flush()?;
-                }
-                None => (),
+    }
+
+    /// Return the path of the file in the backend, with the extension of its codec
+    pub fn storage_path(&self) -> PathBuf {
+        match self.codec {
+            Some(codec) => {
+                let mut path = self.path.clone().into_os_string();
+                path.push(format!(".{}", codec.extension()));
+                PathBuf::from(path)
             }
+            None => self.path.clone(),
+        }
+    }
 
-            Ok(())
-        } else {
-            println!("You cannot save a downloaded file using the asset manager.");
-            Ok(())
+    /// Read the data of the file from a backend
+    pub fn load_from(&mut self, backend: &dyn Backend) -> Result<()> {
+        let data = backend.read(&self.storage_path())?;
+        self.data = Some(match self.codec {
+            Some(codec) => codec.decompress(&data)?,
+            None => data,
+        });
+        self.dirty = false;
+        Ok(())
+    }
+
+    pub fn save(&mut self) -> Result<()> {
+        self.save_to(&FsBackend::default())
+    }
+
+    /// Write the data of the file to a backend
+    pub fn save_to(&mut self, backend: &dyn Backend) -> Result<()> {
+        if self.downloaded {
+            return Err(Error::Unsupported(format!(
+                "\"{}\" was downloaded and cannot be saved",
+                self.path.display()
+            )));
+        }
+        if self.from_archive {
+            return Err(Error::Unsupported(format!(
+                "\"{}\" is in an archive, use AssetsManager::save",
+                self.path.display()
+            )));
         }
+
+        let data = self.data.as_deref().unwrap_or_default();
+        match self.codec {
+            Some(codec) => backend.write(&self.storage_path(), &codec.compress(data)?)?,
+            None => backend.write(&self.path, data)?,
+        }
+        self.dirty = false;
+
+        Ok(())
     }
 }
 
@@ -58,345 +110,1219 @@ impl File {
 pub struct AssetsManager {
     pub index: Index,
     pub cache: DecompressionManager,
-    files: Vec<File>,
+    files: SlotMap<File>,
     pub extension_list: Vec<Box<dyn Extension>>,
     compression_formats: Vec<String>,
     downloader: Downloader,
+    loaders: Vec<Box<dyn AssetLoader>>,
+    background: BackgroundLoader,
+    memory_budget: Option<usize>,
+    /// The number of bytes of the files loaded, and the bytes counted for each file
+    memory_used: usize,
+    file_sizes: HashMap<AssetHandle, usize>,
+    access_counter: u64,
+    watcher: Option<Watcher>,
+    changes: Vec<AssetChange>,
+    mounts: Option<Arc<OverlayBackend>>,
 }
 
 impl Default for AssetsManager {
     fn default() -> Self {
-        return Self::new(Index::new("./", ""), DecompressionManager::default());
+        let index = Index::new("./", "").expect("An empty regex is always valid");
+        Self::new(index, DecompressionManager::default())
     }
 }
 
 impl AssetsManager {
-    pub fn new(index: Index, cache: DecompressionManager) -> Self {
+    /// Create a manager, the decompression manager use the backend of the index
+    pub fn new(mut index: Index, mut cache: DecompressionManager) -> Self {
+        let compression_formats = archive::default_formats();
+        index.set_compression_formats(compression_formats.clone());
+        cache.set_backend(index.backend());
         Self {
             index,
             cache,
-            files: Vec::new(),
+            files: SlotMap::default(),
             extension_list: Vec::new(),
-            compression_formats: vec![String::from("zip")],
+            compression_formats,
             downloader: Downloader::default(),
+            loaders: vec![Box::<JsonLoader>::default(), Box::<CsvLoader>::default()],
+            background: BackgroundLoader::default(),
+            memory_budget: None,
+            memory_used: 0,
+            file_sizes: HashMap::new(),
+            access_counter: 0,
+            watcher: None,
+            changes: Vec::new(),
+            mounts: None,
+        }
+    }
+
+    /// Set the storage used by the index, the loading and the saving of the files
+    pub fn set_backend(&mut self, backend: Arc<dyn Backend>) {
+        self.index.set_backend(backend.clone());
+        self.cache.set_backend(backend);
+        self.mounts = None;
+    }
+
+    pub fn backend(&self) -> Arc<dyn Backend> {
+        self.index.backend()
+    }
+
+    /// Add a layer of content, the files are read from the layer with the highest priority containing them.
+    /// The first mount replace the backend of the manager by the stack of mounts.
+    /// The index is updated, and the loaded files supplied by another layer are reloaded.
+    pub fn mount(&mut self, name: &str, backend: Arc<dyn Backend>, priority: i32) -> Result<()> {
+        let mounts = match &self.mounts {
+            Some(mounts) => mounts.clone(),
+            None => {
+                let mounts = Arc::new(OverlayBackend::new());
+                self.set_backend(mounts.clone());
+                self.mounts = Some(mounts.clone());
+                mounts
+            }
+        };
+
+        let previous = self.loaded_layers();
+        mounts.mount(name, backend, priority);
+        self.refresh_mounts(previous)
+    }
+
+    /// Mount a directory, its content is accessed as if it was in the root of the index
+    pub fn mount_dir(&mut self, name: &str, directory: &str, priority: i32) -> Result<()> {
+        if !std::fs::metadata(directory)?.is_dir() {
+            return Err(Error::Unsupported(format!(
+                "\"{}\" is not a directory",
+                directory
+            )));
         }
+        self.mount(name, Arc::new(FsBackend::new(directory)), priority)
     }
 
-    pub fn move_file(&mut self, origin: &str, target: &str) -> std::io::Result<()> {
-        let origin = self.index.get_path(origin).unwrap();
-        std::fs::copy(origin.clone(), target.clone())?;
-        std::fs::remove_file(origin.clone())?;
+    /// Mount the content of a zip archive, read only
+    pub fn mount_zip(&mut self, name: &str, archive: &str, priority: i32) -> Result<()> {
+        self.mount(name, Arc::new(ZipBackend::open(archive)?), priority)
+    }
 
-        self.index.remove_indexed_file(&origin);
-        let target_path = PathBuf::from(target);
-        if !target_path.exists() {
-            self.index.add_file(target_path);
+    /// Remove a layer, and return true if it was mounted
+    pub fn unmount(&mut self, name: &str) -> Result<bool> {
+        let mounts = match &self.mounts {
+            Some(mounts) => mounts.clone(),
+            None => return Ok(false),
+        };
+
+        let previous = self.loaded_layers();
+        if mounts.unmount(name).is_none() {
+            return Ok(false);
+        }
+        self.refresh_mounts(previous)?;
+        Ok(true)
+    }
+
+    /// Return the layers, from the highest priority to the lowest
+    pub fn mounts(&self) -> Vec<Mount> {
+        match &self.mounts {
+            Some(mounts) => mounts.mounts(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Return the name of the layer supplying the file
+    pub fn layer_of(&self, path: &str) -> Option<String> {
+        let path = self.index.get_path(path).ok()?;
+        self.mounts.as_ref()?.layer_of(Path::new(&path))
+    }
+
+    /// Return the layer supplying each loaded file, or the archive containing it
+    fn loaded_layers(&self) -> Vec<(PathBuf, Option<String>)> {
+        let mounts = match &self.mounts {
+            Some(mounts) => mounts,
+            None => return Vec::new(),
+        };
+
+        let mut layers = Vec::<(PathBuf, Option<String>)>::new();
+        for file in self.files.values() {
+            if file.downloaded || !self.is_file_loaded(file) {
+                continue;
+            }
+            let path = match &file.archive {
+                Some(archive) => archive.clone(),
+                None => file.storage_path(),
+            };
+            if !layers.iter().any(|(loaded, _)| *loaded == path) {
+                let layer = mounts.layer_of(&path);
+                layers.push((path, layer));
+            }
+        }
+        layers
+    }
+
+    /// Update the index after a change of the mounts, and reload the files now supplied by another layer
+    fn refresh_mounts(&mut self, previous: Vec<(PathBuf, Option<String>)>) -> Result<()> {
+        let mounts = match &self.mounts {
+            Some(mounts) => mounts.clone(),
+            None => return Ok(()),
+        };
+
+        let found = if mounts.mounts().is_empty() {
+            Vec::new()
+        } else {
+            self.index.search_in_dir(self.index.root.clone())?
+        };
+        let found_set: HashSet<&PathBuf> = found.iter().collect();
+        let root = self.index.root.clone();
+        self.index
+            .retain(|file| !file.starts_with(&root) || found_set.contains(file));
+        self.index.add_files(found);
+
+        for (path, layer) in previous {
+            let current = mounts.layer_of(&path);
+            if current == layer {
+                continue;
+            }
+            let kind = match current {
+                Some(_) => {
+                    self.reload_changed(&path)?;
+                    ChangeKind::Modified
+                }
+                // Keep the data loaded, the file is only removed from the index
+                None => ChangeKind::Removed,
+            };
+            self.changes.push(AssetChange { path, kind });
         }
 
         Ok(())
     }
 
-    pub fn remove_file(&mut self, origin: &str) -> std::io::Result<()> {
-        let origin = self.index.get_path(origin).unwrap();
-        std::fs::remove_file(origin.clone())?;
+    pub fn move_file(&mut self, origin: &str, target: &str) -> Result<()> {
+        let origin = self.indexed_path(origin)?;
+        let backend = self.backend();
+        let data = backend.read(Path::new(&origin))?;
+        backend.write(Path::new(target), &data)?;
+        backend.remove(Path::new(&origin))?;
+
         self.index.remove_indexed_file(&origin);
+        if !self.index.have_file(target) {
+            self.index.add_file(PathBuf::from(target));
+        }
 
         Ok(())
     }
 
-    pub fn copy_file(&mut self, origin: &str, target: &str) -> std::io::Result<()> {
-        let origin = self.index.get_path(origin).unwrap();
-        std::fs::copy(origin.clone(), target.clone())?;
+    pub fn remove_file(&mut self, origin: &str) -> Result<()> {
+        let origin = self.indexed_path(origin)?;
+        let backend = self.backend();
+        backend.remove(Path::new(&origin))?;
+        // The file of a lower layer can become visible
+        if !backend.exists(Path::new(&origin)) {
+            self.index.remove_indexed_file(&origin);
+        }
+
+        Ok(())
+    }
+
+    pub fn copy_file(&mut self, origin: &str, target: &str) -> Result<()> {
+        let origin = self.indexed_path(origin)?;
+        let backend = self.backend();
+        let data = backend.read(Path::new(&origin))?;
+        backend.write(Path::new(target), &data)?;
 
         self.index.add_file(PathBuf::from(target));
 
         Ok(())
     }
 
-    pub fn create_file(&mut self, path: &str) -> std::io::Result<()> {
-        std::fs::File::options()
-            .create(true)
-            .write(true)
-            .open(path)?;
+    pub fn create_file(&mut self, path: &str) -> Result<()> {
+        let backend = self.backend();
+        if !backend.exists(Path::new(path)) {
+            backend.write(Path::new(path), &[])?;
+        }
         self.index.add_file(PathBuf::from(path));
 
         Ok(())
     }
 
     pub fn remove_process_pass(&mut self, name: &str) {
-        for i in 0..self.extension_list.len() {
-            if self.extension_list[i].get_name() == name {
-                self.extension_list.remove(i);
-            }
-        }
+        self.extension_list
+            .retain_mut(|extension| extension.get_name() != name);
     }
 
     pub fn add_compression_formats(&mut self, format: &str) {
         self.compression_formats.push(String::from(format));
+        self.index
+            .set_compression_formats(self.compression_formats.clone());
     }
 
     pub fn add_extension(&mut self, extension: Box<dyn Extension>) {
         self.extension_list.push(extension);
     }
 
-    pub fn load(&mut self, base_path: &str) -> std::io::Result<()> {
-        if (base_path.starts_with("http://") || base_path.starts_with("https://"))
-            && self.downloader.can_download(&base_path)
+    /// Add a loader used by get_typed, it takes priority over the previous ones
+    pub fn add_loader(&mut self, loader: Box<dyn AssetLoader>) {
+        self.loaders.insert(0, loader);
+    }
+
+    /// Load a file, and return the handle to access it without path resolution
+    pub fn load(&mut self, base_path: &str) -> Result<AssetHandle> {
+        let source = self.resolve_source(base_path)?;
+        let handle = self.load_source(source)?;
+        self.prepare_access(handle)?;
+        Ok(handle)
+    }
+
+    fn load_source(&mut self, source: LoadSource) -> Result<AssetHandle> {
+        if let Some(handle) = self.find_loaded_source(&source) {
+            return Ok(handle);
+        }
+
+        let data = match &source {
+            LoadSource::Download(url) => self.downloader.download_to_memory(url)?,
+            LoadSource::Disk(path) => {
+                let mut file = File {
+                    path: path.clone(),
+                    ..Default::default()
+                };
+                file.load_from(self.backend().as_ref())?;
+                file.data.unwrap_or_default()
+            }
+            LoadSource::Compressed { path, codec } => {
+                let mut file = File {
+                    path: path.clone(),
+                    codec: Some(*codec),
+                    ..Default::default()
+                };
+                file.load_from(self.backend().as_ref())?;
+                file.data.unwrap_or_default()
+            }
+            LoadSource::Archive { archive, entry } => {
+                self.cache
+                    .load_archive(archive, Some(vec![entry]), &mut self.extension_list)?;
+                // The extensions add the files using the name of the entry
+                self.cache
+                    .rename(entry, &DecompressionManager::entry_path(archive, entry));
+                return Ok(self.insert_file(File {
+                    from_archive: true,
+                    archive: Some(PathBuf::from(archive)),
+                    path: PathBuf::from(entry),
+                    ..Default::default()
+                }));
+            }
+        };
+
+        Ok(self.insert_loaded(source, data))
+    }
+
+    /// Start to load a file on the worker threads, the result is available using poll or drain_completed.
+    /// Archives not supported natively are loaded immediately, because extensions run on the main thread.
+    pub fn load_async(&mut self, base_path: &str) -> Result<LoadTicket> {
+        let source = self.resolve_source(base_path)?;
+        let ticket = self.background.new_ticket();
+
+        if let Some(handle) = self.find_loaded_source(&source) {
+            let result = self.prepare_access(handle).map(|_| handle);
+            self.background.complete(ticket, result);
+            return Ok(ticket);
+        }
+
+        match source {
+            LoadSource::Archive { ref archive, .. }
+                if ArchiveFormat::from_path(Path::new(archive)).is_none() =>
+            {
+                let result = self.load_source(source);
+                let result = result.and_then(|handle| self.prepare_access(handle).map(|_| handle));
+                self.background.complete(ticket, result);
+            }
+            source => {
+                let job_source = source.clone();
+                let backend = self.backend();
+                self.background
+                    .spawn(ticket, source, move || match job_source {
+                        LoadSource::Download(url) => Downloader::new().download_to_memory(&url),
+                        LoadSource::Disk(path) => backend.read(&path),
+                        LoadSource::Compressed { path, codec } => {
+                            let mut file = File {
+                                path,
+                                codec: Some(codec),
+                                ..Default::default()
+                            };
+                            file.load_from(backend.as_ref())?;
+                            Ok(file.data.unwrap_or_default())
+                        }
+                        LoadSource::Archive { archive, entry } => {
+                            DecompressionManager::read_entry(backend.as_ref(), &archive, &entry)
+                        }
+                    })?;
+            }
+        }
+
+        Ok(ticket)
+    }
+
+    /// Return the result of the loading if it is finished, the result is returned only once
+    pub fn poll(&mut self, ticket: LoadTicket) -> Option<Result<AssetHandle>> {
+        self.receive_loaded();
+        self.background.take(ticket)
+    }
+
+    /// Return true if the loading is finished and not yet polled
+    pub fn is_ready(&mut self, ticket: LoadTicket) -> bool {
+        self.receive_loaded();
+        self.background.is_completed(ticket)
+    }
+
+    /// Return all the loadings finished since the last call
+    pub fn drain_completed(&mut self) -> Vec<(LoadTicket, Result<AssetHandle>)> {
+        self.receive_loaded();
+        self.background.drain()
+    }
+
+    /// Number of loadings still running on the worker threads
+    pub fn pending_loads(&self) -> usize {
+        self.background.pending()
+    }
+
+    /// Set the number of worker threads used by load_async, 0 let rayon choose it
+    pub fn set_worker_count(&mut self, worker_count: usize) {
+        self.background.set_worker_count(worker_count);
+    }
+
+    /// Add the files read by the workers to the manager
+    fn receive_loaded(&mut self) {
+        while let Some(loaded) = self.background.try_recv() {
+            let result = loaded.data.and_then(|data| {
+                // The file loaded, or modified, since the loading started is kept
+                let handle = match self.find_loaded_source(&loaded.source) {
+                    Some(handle) => handle,
+                    None => self.insert_loaded(loaded.source, data),
+                };
+                self.prepare_access(handle).map(|_| handle)
+            });
+            self.background.complete(loaded.ticket, result);
+        }
+    }
+
+    fn insert_loaded(&mut self, source: LoadSource, data: Vec<u8>) -> AssetHandle {
+        match source {
+            LoadSource::Download(url) => {
+                let file_path = PathBuf::from(&url);
+                self.index.add_file(file_path.clone());
+                self.insert_file(File {
+                    path: file_path,
+                    data: Some(data),
+                    downloaded: true,
+                    ..Default::default()
+                })
+            }
+            LoadSource::Disk(path) => self.insert_file(File {
+                path,
+                data: Some(data),
+                ..Default::default()
+            }),
+            LoadSource::Compressed { path, codec } => self.insert_file(File {
+                path,
+                data: Some(data),
+                codec: Some(codec),
+                ..Default::default()
+            }),
+            LoadSource::Archive { archive, entry } => {
+                self.cache
+                    .add_file(&DecompressionManager::entry_path(&archive, &entry), data);
+                self.insert_file(File {
+                    from_archive: true,
+                    archive: Some(PathBuf::from(archive)),
+                    path: PathBuf::from(entry),
+                    ..Default::default()
+                })
+            }
+        }
+    }
+
+    /// Add the file, or replace the file with the same path to keep its handle and holders
+    fn insert_file(&mut self, file: File) -> AssetHandle {
+        let handle = match self.find_file_index_using_full_path(&file.full_path().to_string_lossy())
         {
-            self.downloader.download_sync(
-                base_path.to_string(),
-                String::from("FastAssetAutoDownload_temp.tmp"),
-            );
-            let downloaded_content = std::fs::read("FastAssetAutoDownload_temp.tmp");
-            match downloaded_content {
-                Err(err) => {
-                    println!("Error while reading downloaded file: {}", err);
-                }
-                Ok(content) => {
-                    let file_path = PathBuf::from(base_path);
-                    let new_file = File {
-                        from_archive: false,
-                        path: file_path.clone(),
-                        data: Some(content),
-                        downloaded: true,
-                    };
-                    self.index.files.push(file_path);
-                    self.files.push(new_file);
-                    return Ok(());
+            Some(handle) => {
+                if let Some(existing) = self.files.get_mut(handle) {
+                    let ref_count = existing.ref_count;
+                    *existing = File { ref_count, ..file };
                 }
+                handle
             }
-        }
+            None => self.files.insert(file),
+        };
+        self.track_memory(handle);
+        handle
+    }
 
-        let mut path;
-        if !(base_path.contains('\\') || base_path.contains('/')) {
-            path = self.index.get_path(base_path);
+    /// Return the handle of the file if it is already loaded from this source, or modified
+    fn find_loaded_source(&self, source: &LoadSource) -> Option<AssetHandle> {
+        let handle = self.find_file_index_using_full_path(&source.file_path().to_string_lossy())?;
+        let file = self.files.get(handle)?;
+        if file.dirty || self.is_file_loaded(file) {
+            Some(handle)
         } else {
-            path = Some(String::from(base_path));
+            None
         }
+    }
 
-        for i in 0..self.extension_list.len() {
-            let mut process_pass = self.extension_list.swap_remove(i);
-            if !process_pass.on_load(self, &mut path) {
-                return Ok(());
+    /// Return true if the file has data, in memory or in the decompression cache
+    fn is_file_loaded(&self, file: &File) -> bool {
+        if file.evicted {
+            true
+        } else if file.from_archive {
+            match self.cache.get(&file.full_path().to_string_lossy()) {
+                Some(cached) => cached.data.is_some() || cached.cache.is_some(),
+                None => false,
             }
-            self.extension_list.insert(i, process_pass);
+        } else {
+            file.data.is_some()
+        }
+    }
+
+    /// Load the file if needed and add a holder to it, the file stay loaded until all the holders release it
+    pub fn acquire(&mut self, path: &str) -> Result<AssetHandle> {
+        let handle = self.load(path)?;
+        if let Some(file) = self.files.get_mut(handle) {
+            file.ref_count += 1;
+        }
+        Ok(handle)
+    }
+
+    /// Remove a holder of the file, and unload it if it was the last one.
+    /// Return true if the file was unloaded.
+    pub fn release(&mut self, path: &str) -> Result<bool> {
+        match self.find_loaded(path)? {
+            Some(handle) => self.release_by_handle(handle),
+            None => Ok(false),
         }
+    }
 
-        match path {
-            Some(path) => {
-                let path = PathBuf::from(path);
+    /// Same as release, but using a handle
+    pub fn release_by_handle(&mut self, handle: AssetHandle) -> Result<bool> {
+        let file = self
+            .files
+            .get_mut(handle)
+            .ok_or(Error::InvalidHandle(handle))?;
+        if file.ref_count == 0 {
+            return Ok(false);
+        }
 
-                let mut in_archive: Option<String> = None;
-                let mut path_until_archive = Vec::<String>::new();
-                let mut path_in_archive = Vec::<String>::new();
-                for i in path.components() {
-                    let cmp = i.as_os_str().to_string_lossy();
+        file.ref_count -= 1;
+        if file.ref_count > 0 {
+            return Ok(false);
+        }
 
-                    if in_archive.is_none() {
-                        path_until_archive.push(cmp.to_string());
-                    } else {
-                        path_in_archive.push(cmp.to_string());
-                    }
+        let path = file.full_path().to_string_lossy().to_string();
+        self.unload(&path, false)?;
+        Ok(true)
+    }
 
-                    for fmt in self.compression_formats.iter() {
-                        if cmp.ends_with(&format!(".{}", fmt)) {
-                            in_archive = Some(i.as_os_str().to_string_lossy().to_string());
-                        }
+    /// Acquire the dependencies of a file in the order given by DependencieManager::load_order, then the file.
+    /// If a file cannot be loaded, the files already acquired are released.
+    pub fn load_with_dependencies(
+        &mut self,
+        path: &str,
+        dependencies: &DependencieManager,
+    ) -> Result<AssetHandle> {
+        let order = dependencies.load_order(path)?;
+        let mut handles = Vec::<AssetHandle>::new();
+        for file in order.iter() {
+            match self.acquire(file) {
+                Ok(handle) => handles.push(handle),
+                Err(err) => {
+                    for handle in handles.into_iter().rev() {
+                        self.release_by_handle(handle)?;
                     }
+                    return Err(err);
                 }
+            }
+        }
 
-                match in_archive {
-                    Some(_) => {
-                        let mut file = File::default();
-
-                        let mut archive = String::new();
-                        path_until_archive.iter().for_each(|elem| {
-                            archive.push_str(&format!("{}/", elem));
-                        });
-                        archive.pop();
-                        file.from_archive = true;
-
-                        let mut path = String::new();
-                        path_in_archive.iter().for_each(|elem| {
-                            path.push_str(&format!("{}/", elem));
-                        });
-                        path.pop();
-                        file.path = PathBuf::from(path.clone());
-
-                        self.cache.load_archive(
-                            &archive,
-                            Some(vec![&path]),
-                            &mut self.extension_list,
-                        );
-
-                        file.from_archive = true;
-                        file.path = PathBuf::from(path);
-                        self.files.push(file);
-                    }
-                    None => {
-                        let mut file = File::default();
-                        file.path = path;
-                        file.load()?;
-                        self.files.push(file);
+        handles
+            .pop()
+            .ok_or_else(|| Error::NotIndexed(path.to_string()))
+    }
+
+    /// Release a file loaded with load_with_dependencies, and its dependencies.
+    /// The dependencies still acquired by other files stay loaded.
+    /// Return the files unloaded.
+    pub fn unload_with_dependencies(
+        &mut self,
+        path: &str,
+        dependencies: &DependencieManager,
+    ) -> Result<Vec<String>> {
+        let mut unloaded = Vec::<String>::new();
+        for file in dependencies.load_order(path)?.into_iter().rev() {
+            if self.release(&file)? {
+                unloaded.push(file);
+            }
+        }
+        Ok(unloaded)
+    }
+
+    /// Return the number of holders of the file
+    pub fn ref_count(&self, handle: AssetHandle) -> usize {
+        self.files.get(handle).map_or(0, |file| file.ref_count)
+    }
+
+    /// Start to watch the changes of the files in the root of the index, using check_changes
+    pub fn enable_hot_reload(&mut self) -> Result<()> {
+        self.watcher = Some(Watcher::new(&self.index)?);
+        Ok(())
+    }
+
+    pub fn disable_hot_reload(&mut self) {
+        self.watcher = None;
+    }
+
+    pub fn is_hot_reload_enabled(&self) -> bool {
+        self.watcher.is_some()
+    }
+
+    /// Look for the files changed since the last check (if hot reload is enabled),
+    /// update the index, and reload the files loaded and the entries of the archives modified
+    pub fn check_changes(&mut self) -> Result<()> {
+        let changes = match &mut self.watcher {
+            Some(watcher) => watcher.scan(&self.index)?,
+            None => return Ok(()),
+        };
+
+        if !changes.is_empty() {
+            self.cache.clear_archive_cache();
+        }
+
+        for change in changes {
+            match change.kind {
+                ChangeKind::Added => {
+                    if !self.index.have_file(&change.path.to_string_lossy()) {
+                        self.index.add_file(change.path.clone());
                     }
                 }
+                ChangeKind::Removed => {
+                    self.index
+                        .remove_indexed_file(&change.path.to_string_lossy());
+                }
+                ChangeKind::Modified => self.reload_changed(&change.path)?,
+            }
+            self.changes.push(change);
+        }
+
+        Ok(())
+    }
+
+    /// Return the changes detected by check_changes since the last call
+    pub fn changed_assets(&mut self) -> Vec<AssetChange> {
+        std::mem::take(&mut self.changes)
+    }
+
+    /// Reload the loaded files from the path, or from the archive at the path
+    fn reload_changed(&mut self, path: &Path) -> Result<()> {
+        let mut entries = Vec::<(AssetHandle, PathBuf, PathBuf)>::new();
+        let mut files = Vec::<AssetHandle>::new();
+        for (handle, file) in self.files.iter() {
+            if file.downloaded || !self.is_file_loaded(file) {
+                continue;
+            }
+            match &file.archive {
+                // Only the entries of the formats supported natively can be read without the extensions,
+                // the archive can be the modified file or contained in it
+                Some(archive)
+                    if normalize(archive).starts_with(normalize(path))
+                        && ArchiveFormat::from_path(archive).is_some() =>
+                {
+                    entries.push((handle, archive.clone(), file.path.clone()))
+                }
+                None if same_path(&file.storage_path(), path) => files.push(handle),
+                _ => (),
+            }
+        }
+
+        let backend = self.backend();
+        for handle in files {
+            if let Some(file) = self.files.get_mut(handle) {
+                file.load_from(backend.as_ref())?;
+                file.typed = None;
+                file.evicted = false;
             }
-            None => {}
+            self.track_memory(handle);
+        }
+
+        for (handle, archive, entry) in entries {
+            let entry = entry.to_string_lossy().to_string();
+            let data = DecompressionManager::read_entry(
+                backend.as_ref(),
+                &archive.to_string_lossy(),
+                &entry,
+            )?;
+            let path = archive.join(&entry);
+            self.cache.add_file(&path.to_string_lossy(), data);
+            if let Some(file) = self.files.get_mut(handle) {
+                file.typed = None;
+                file.evicted = false;
+            }
+            self.track_memory(handle);
+            self.changes.push(AssetChange {
+                path,
+                kind: ChangeKind::Modified,
+            });
         }
 
         Ok(())
     }
 
-    pub fn unload(&mut self, mut path: &str, mut cache_decompressed: bool) {
+    /// Find where the file must be loaded from, running the extensions
+    fn resolve_source(&mut self, base_path: &str) -> Result<LoadSource> {
+        if base_path.starts_with("http://") || base_path.starts_with("https://") {
+            return Ok(LoadSource::Download(base_path.to_string()));
+        }
+
+        let mut codec = None;
+        let mut path = if !(base_path.contains('\\') || base_path.contains('/')) {
+            // Let the extensions handle the files not indexed
+            match self.index.get_path(base_path) {
+                Ok(path) => Some(path),
+                Err(Error::NotIndexed(_)) => match self.find_compressed(base_path)? {
+                    Some((path, found)) => {
+                        codec = Some(found);
+                        Some(path)
+                    }
+                    None => None,
+                },
+                Err(err) => return Err(err),
+            }
+        } else {
+            let backend = self.backend();
+            let compressed = match backend.exists(Path::new(base_path)) {
+                true => None,
+                false => Codec::all().into_iter().find(|codec| {
+                    backend.exists(Path::new(&format!("{}.{}", base_path, codec.extension())))
+                }),
+            };
+            match compressed {
+                Some(found) => {
+                    codec = Some(found);
+                    Some(format!("{}.{}", base_path, found.extension()))
+                }
+                None => Some(String::from(base_path)),
+            }
+        };
+
+        for i in 0..self.extension_list.len() {
+            let mut process_pass = self.extension_list.swap_remove(i);
+            if !process_pass.on_load(self, &mut path) {
+                return Err(Error::Cancelled(base_path.to_string()));
+            }
+            self.extension_list.insert(i, process_pass);
+        }
+
+        let path = match path {
+            Some(path) => PathBuf::from(path),
+            None => return Err(Error::NotIndexed(base_path.to_string())),
+        };
+
+        // Split at the last archive, the archives containing it are read from memory
+        let components: Vec<String> = path
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().to_string())
+            .collect();
+        let last_archive = components
+            .iter()
+            .take(components.len().saturating_sub(1))
+            .rposition(|component| {
+                self.compression_formats
+                    .iter()
+                    .any(|fmt| component.ends_with(&format!(".{}", fmt)))
+            });
+
+        if let Some(i) = last_archive {
+            // Collected as a path to keep the root of the absolute paths
+            let archive: PathBuf = path.components().take(i + 1).collect();
+            return Ok(LoadSource::Archive {
+                archive: archive.to_string_lossy().to_string(),
+                entry: components[i + 1..].join("/"),
+            });
+        }
+
+        // The path of the decompressed file, if the extensions kept the compressed one
+        if let Some(codec) = codec {
+            let path = path.to_string_lossy().to_string();
+            if let Some(path) = path.strip_suffix(&format!(".{}", codec.extension())) {
+                return Ok(LoadSource::Compressed {
+                    path: PathBuf::from(path),
+                    codec,
+                });
+            }
+        }
+
+        Ok(LoadSource::Disk(path))
+    }
+
+    /// Find a compressed variant of a file in the index, like "level.bin.zst" for "level.bin"
+    fn find_compressed(&self, filename: &str) -> Result<Option<(String, Codec)>> {
+        for codec in Codec::all() {
+            match self
+                .index
+                .get_path(&format!("{}.{}", filename, codec.extension()))
+            {
+                Ok(path) => return Ok(Some((path, codec))),
+                Err(Error::NotIndexed(_)) => (),
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn unload(&mut self, mut path: &str, mut cache_decompressed: bool) -> Result<()> {
         for i in 0..self.extension_list.len() {
             let mut process_pass = self.extension_list.swap_remove(i);
             if !process_pass.on_unload(self, &mut path, &mut cache_decompressed) {
-                return;
+                return Ok(());
             }
             self.extension_list.insert(i, process_pass);
         }
 
-        for file in self.files.iter_mut() {
-            let file_path = file.path.to_string_lossy();
+        let mut unloaded = Vec::<AssetHandle>::new();
+        for (handle, file) in self.files.iter_mut() {
+            let file_path = file.full_path().to_string_lossy().to_string();
             if path == file_path {
                 file.data = None;
+                file.typed = None;
+                file.evicted = false;
                 if file.from_archive {
                     if cache_decompressed {
-                        self.cache.cache(&file_path);
+                        self.cache.cache(&file_path)?;
                     } else {
                         self.cache.unload(&file_path);
                     }
                 }
+                unloaded.push(handle);
             }
         }
+        for handle in unloaded {
+            self.track_memory(handle);
+        }
+
+        Ok(())
     }
 
-    pub fn remove(&mut self, mut path: &str) {
+    pub fn remove(&mut self, mut path: &str) -> Result<()> {
         for i in 0..self.extension_list.len() {
             let mut process_pass = self.extension_list.swap_remove(i);
             if !process_pass.on_remove(self, &mut path) {
-                return;
+                return Ok(());
             }
             self.extension_list.insert(i, process_pass);
         }
 
-        for i in 0..self.files.len() {
-            if i < self.files.len() {
-                let file_path = self.files[i].path.to_string_lossy();
-                if path == file_path {
-                    if self.files[i].from_archive {
-                        self.cache.remove(path);
-                    }
-                    self.files.remove(i);
-                }
+        while let Some(handle) = self.find_file_index_using_full_path(path) {
+            if self.ref_count(handle) > 0 {
+                return Err(Error::InUse(path.to_string()));
             }
+            self.remove_file_entry(handle);
         }
+
+        Ok(())
     }
 
-    pub fn find_file_index(&self, filename: &str) -> Option<usize> {
-        for i in 0..self.files.len() {
-            if self.files[i].path.file_name().unwrap().to_string_lossy() == filename {
-                return Some(i);
+    /// Remove the file referenced by the handle, the handle and its copies become invalid
+    pub fn remove_by_handle(&mut self, handle: AssetHandle) -> Result<()> {
+        let path = match self.files.get(handle) {
+            Some(file) if file.ref_count > 0 => {
+                return Err(Error::InUse(file.path.to_string_lossy().to_string()))
             }
+            Some(file) => file.path.to_string_lossy().to_string(),
+            None => return Err(Error::InvalidHandle(handle)),
+        };
+
+        let mut path = path.as_str();
+        for i in 0..self.extension_list.len() {
+            let mut process_pass = self.extension_list.swap_remove(i);
+            if !process_pass.on_remove(self, &mut path) {
+                return Ok(());
+            }
+            self.extension_list.insert(i, process_pass);
         }
-        None
+
+        self.remove_file_entry(handle);
+        Ok(())
     }
 
-    pub fn find_file_index_using_full_path(&self, path: &str) -> Option<usize> {
-        for i in 0..self.files.len() {
-            if self.files[i].path.to_string_lossy() == path {
-                return Some(i);
+    fn remove_file_entry(&mut self, handle: AssetHandle) {
+        if let Some(file) = self.files.remove(handle) {
+            if file.from_archive {
+                self.cache.remove(&file.full_path().to_string_lossy());
             }
         }
-        None
+        self.track_memory(handle);
     }
 
-    pub fn get(&mut self, path: &str) -> Option<Vec<u8>> {
-        let in_cache = self.cache.get_data(path);
-        return match in_cache {
-            Some(_) => in_cache,
-            None => {
-                let path = self
-                    .index
-                    .get_path(path)
-                    .expect(&format!("Cannot get path of: {}", path));
-                let index;
-                if path.contains('\\') || path.contains('/') {
-                    index = self.find_file_index_using_full_path(path.as_str());
-                } else {
-                    index = self.find_file_index(path.as_str());
+    pub fn find_file_index(&self, filename: &str) -> Option<AssetHandle> {
+        self.files.find(|file| match file.path.file_name() {
+            Some(name) => name.to_string_lossy() == filename,
+            None => false,
+        })
+    }
+
+    pub fn find_file_index_using_full_path(&self, path: &str) -> Option<AssetHandle> {
+        self.files
+            .find(|file| file.full_path().to_string_lossy() == path)
+    }
+
+    /// Return true if the handle still reference a loaded file
+    pub fn is_handle_valid(&self, handle: AssetHandle) -> bool {
+        self.files.contains(handle)
+    }
+
+    /// Return the file referenced by the handle
+    pub fn get_file_by_handle(&self, handle: AssetHandle) -> Option<&File> {
+        self.files.get(handle)
+    }
+
+    /// Return a ref of the data in O(1), and auto manage the cache for compressed files
+    pub fn get_by_handle(&mut self, handle: AssetHandle) -> Result<&Option<Vec<u8>>> {
+        self.prepare_access(handle)?;
+        let file = self.files.get(handle).ok_or(Error::InvalidHandle(handle))?;
+        if file.from_archive {
+            let path = file.full_path().to_string_lossy().to_string();
+            if self.cache.get(&path).is_some() {
+                if let Some(data) = self.cache.get_data_ref(&path)? {
+                    return Ok(data);
                 }
-                if index.is_none() {
-                    return None;
+            }
+        }
+
+        match self.files.get(handle) {
+            Some(file) => Ok(&file.data),
+            None => Err(Error::InvalidHandle(handle)),
+        }
+    }
+
+    /// Return a &mut of the data in O(1), and auto manage the cache for compressed files
+    pub fn get_mut_by_handle(&mut self, handle: AssetHandle) -> Result<&mut Option<Vec<u8>>> {
+        self.prepare_access(handle)?;
+        let file = self
+            .files
+            .get_mut(handle)
+            .ok_or(Error::InvalidHandle(handle))?;
+        // The data can be modified, so the decoded value is no longer valid,
+        // and the file cannot be evicted until saved
+        file.typed = None;
+        file.dirty = true;
+        if file.from_archive {
+            let path = file.full_path().to_string_lossy().to_string();
+            if self.cache.get(&path).is_some() {
+                if let Some(data) = self.cache.get_data_mut(&path)? {
+                    return Ok(data);
                 }
-                self.files[index.unwrap()].data.clone()
             }
+        }
+
+        match self.files.get_mut(handle) {
+            Some(file) => Ok(&mut file.data),
+            None => Err(Error::InvalidHandle(handle)),
+        }
+    }
+
+    pub fn get(&mut self, path: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self.get_ref(path)?.and_then(|data| data.clone()))
+    }
+
+    pub fn get_ref(&mut self, path: &str) -> Result<Option<&Option<Vec<u8>>>> {
+        match self.find_accessible(path)? {
+            Some(handle) => Ok(Some(self.get_by_handle(handle)?)),
+            None => self.cache.get_data_ref(path),
+        }
+    }
+
+    pub fn get_mut(&mut self, path: &str) -> Result<Option<&mut Option<Vec<u8>>>> {
+        match self.find_accessible(path)? {
+            Some(handle) => Ok(Some(self.get_mut_by_handle(handle)?)),
+            None => self.cache.get_data_mut(path),
+        }
+    }
+
+    /// Set the maximum number of bytes loaded, the least recently used files are evicted to respect it.
+    /// Compressed files are put in the decompression cache, other files are unloaded,
+    /// and both are reloaded on the next access.
+    /// Downloaded and modified files are never evicted, and the nested archives are dropped first.
+    pub fn set_memory_budget(&mut self, budget: Option<usize>) -> Result<()> {
+        self.memory_budget = budget;
+        self.enforce_memory_budget(None)
+    }
+
+    pub fn get_memory_budget(&self) -> Option<usize> {
+        self.memory_budget
+    }
+
+    /// Return the number of bytes of the files loaded by the manager, and of the nested archives kept by the decompression cache
+    pub fn memory_usage(&self) -> usize {
+        self.memory_used + self.cache.archive_memory_usage()
+    }
+
+    /// Count the bytes of the file in the memory usage again, after its data is changed
+    fn track_memory(&mut self, handle: AssetHandle) {
+        let size = self
+            .files
+            .get(handle)
+            .map_or(0, |file| self.loaded_size(file));
+        let previous = if size > 0 {
+            self.file_sizes.insert(handle, size)
+        } else {
+            self.file_sizes.remove(&handle)
         };
+        self.memory_used = self.memory_used + size - previous.unwrap_or(0);
     }
 
-    pub fn get_ref(&mut self, path: &str) -> Option<&Option<Vec<u8>>> {
-        let path_buf = self.index.get_path(path).unwrap();
-        let is_full_path = path_buf.contains('\\') || path_buf.contains('/');
-        let in_cache = self.cache.get_data_ref(path);
-        match in_cache {
-            Some(_) => return in_cache,
-            None => {
-                for file in self.files.iter() {
-                    if is_full_path && file.path.to_string_lossy() == path_buf {
-                        return Some(&file.data);
-                    }
-                }
+    /// Mark the file as used, reload it if it was evicted, and evict other files if needed
+    fn prepare_access(&mut self, handle: AssetHandle) -> Result<()> {
+        let backend = self.backend();
+        self.access_counter += 1;
+        let file = self
+            .files
+            .get_mut(handle)
+            .ok_or(Error::InvalidHandle(handle))?;
+        file.last_access = self.access_counter;
+        if file.evicted {
+            if !file.from_archive {
+                file.load_from(backend.as_ref())?;
             }
+            file.evicted = false;
         }
-        None
+        if file.from_archive {
+            let path = file.full_path().to_string_lossy().to_string();
+            // Reload the data from the decompression cache before computing the memory usage
+            self.cache.get_data_ref(&path)?;
+        }
+        self.track_memory(handle);
+        self.enforce_memory_budget(Some(handle))
     }
 
-    pub fn get_mut(&mut self, path: &str) -> Option<&mut Option<Vec<u8>>> {
-        let path_buf = self.index.get_path(path).unwrap();
-        let in_cache = self.cache.get_data_mut(path);
-        match in_cache {
-            Some(_) => return in_cache,
-            None => {
-                for file in self.files.iter_mut() {
-                    if file.path.to_string_lossy() == path_buf {
-                        return Some(&mut file.data);
-                    }
+    /// Evict the least recently used files until the memory usage fit in the budget
+    fn enforce_memory_budget(&mut self, keep: Option<AssetHandle>) -> Result<()> {
+        let budget = match self.memory_budget {
+            Some(budget) => budget,
+            None => return Ok(()),
+        };
+
+        // The nested archives are read again from their parent when needed, so they are evicted first
+        if self.memory_usage() > budget {
+            self.cache.clear_archive_cache();
+        }
+
+        // The files which cannot be written in the decompression cache stay in memory
+        let mut failed = HashSet::<AssetHandle>::new();
+        while self.memory_usage() > budget {
+            let candidate = self
+                .files
+                .iter()
+                .filter(|(handle, file)| {
+                    Some(*handle) != keep
+                        && !failed.contains(handle)
+                        && !file.downloaded
+                        && !file.dirty
+                        && self.file_sizes.contains_key(handle)
+                })
+                .min_by_key(|(_, file)| file.last_access)
+                .map(|(handle, _)| handle);
+
+            let handle = match candidate {
+                Some(handle) => handle,
+                None => break,
+            };
+
+            if let Some(file) = self.files.get_mut(handle) {
+                if file.from_archive
+                    && self
+                        .cache
+                        .cache(&file.full_path().to_string_lossy())
+                        .is_err()
+                {
+                    failed.insert(handle);
+                    continue;
                 }
+                file.typed = None;
+                file.data = None;
+                file.evicted = true;
             }
+            self.track_memory(handle);
         }
-        None
+
+        Ok(())
     }
 
-    pub fn set_data(&mut self, path: &str, new_data: Vec<u8>) {
-        match self.get_mut(path) {
-            Some(data) => {
-                *data = Some(new_data);
-            }
-            None => (),
+    fn loaded_size(&self, file: &File) -> usize {
+        let data = if file.from_archive {
+            self.cache
+                .get(&file.full_path().to_string_lossy())
+                .and_then(|cached| cached.data.as_ref())
+        } else {
+            file.data.as_ref()
+        };
+        data.map_or(0, |data| data.len())
+    }
+
+    /// Replace the data of a loaded file, the file is saved using save
+    pub fn set_data(&mut self, path: &str, new_data: Vec<u8>) -> Result<()> {
+        match self.get_mut(path)? {
+            Some(data) => *data = Some(new_data),
+            None => return Err(Error::NotLoaded(path.to_string())),
+        }
+        if let Some(handle) = self.find_accessible(path)? {
+            self.track_memory(handle);
+            self.enforce_memory_budget(Some(handle))?;
         }
+        Ok(())
     }
 
     pub fn have_file(&self, filename: &str) -> bool {
-        for file in self.files.iter() {
-            if file.path.file_name().unwrap().to_string_lossy() == filename {
-                return true;
-            }
+        let loaded = self.files.values().any(|file| match file.path.file_name() {
+            Some(name) => name.to_string_lossy() == filename,
+            None => false,
+        });
+
+        loaded || self.index.have_file(filename)
+    }
+
+    /// Save a file, the files from a zip archive are written back into the archive
+    pub fn save(&mut self, filename: &str) -> Result<()> {
+        let handle = match self.find_loaded(filename)? {
+            Some(handle) => handle,
+            None => return Ok(()),
+        };
+        let backend = self.backend();
+        let file = self
+            .files
+            .get_mut(handle)
+            .ok_or(Error::InvalidHandle(handle))?;
+        if let (true, Some(archive)) = (file.from_archive, &file.archive) {
+            self.cache
+                .save_entry(&archive.to_string_lossy(), &file.path.to_string_lossy())?;
+            file.dirty = false;
+            return Ok(());
         }
+        file.save_to(backend.as_ref())
+    }
 
-        return self.index.have_file(filename);
+    pub fn get_files_matching_regex(&self, regex: &str) -> Result<Vec<PathBuf>> {
+        self.index.regex_search(regex)
     }
 
-    pub fn save(&mut self, filename: &str) -> std::io::Result<()> {
-        for file in self.files.iter_mut() {
-            if file.path.file_name().unwrap().to_string_lossy() == filename {
-                return file.save();
+    /// Return the value decoded by the AssetLoader matching the file extension,
+    /// or None if the file is not loaded
+    pub fn get_typed<T: Any>(&mut self, path: &str) -> Result<Option<&T>> {
+        match self.find_loaded(path)? {
+            Some(handle) => self.get_typed_by_handle(handle),
+            None => Ok(None),
+        }
+    }
+
+    /// Same as get_typed, but using a handle
+    pub fn get_typed_by_handle<T: Any>(&mut self, handle: AssetHandle) -> Result<Option<&T>> {
+        // Make sure the data is not only in the decompression cache
+        if self.get_by_handle(handle)?.is_none() {
+            return Ok(None);
+        }
+
+        let file = self.files.get(handle).ok_or(Error::InvalidHandle(handle))?;
+        let path = file.path.to_string_lossy().to_string();
+        if file.typed.is_none() {
+            let extension = match file.path.extension() {
+                Some(extension) => extension.to_string_lossy().to_lowercase(),
+                None => String::new(),
+            };
+            let loader = self
+                .loaders
+                .iter()
+                .find(|loader| loader.extensions().contains(&extension))
+                .ok_or_else(|| Error::Unsupported(format!("No loader for \"{}\"", path)))?;
+
+            let data = if file.from_archive {
+                self.cache
+                    .get(&file.full_path().to_string_lossy())
+                    .and_then(|file| file.data.as_ref())
+            } else {
+                file.data.as_ref()
+            };
+            let typed = match data {
+                Some(data) => loader.load(data)?,
+                None => return Ok(None),
+            };
+
+            if let Some(file) = self.files.get_mut(handle) {
+                file.typed = Some(TypedData(typed));
             }
         }
 
-        Ok(())
+        let typed = self.files.get(handle).and_then(|file| file.typed.as_ref());
+        match typed {
+            Some(TypedData(typed)) => match typed.downcast_ref::<T>() {
+                Some(typed) => Ok(Some(typed)),
+                None => Err(Error::Unsupported(format!(
+                    "\"{}\" is not decoded as {}",
+                    path,
+                    std::any::type_name::<T>()
+                ))),
+            },
+            None => Ok(None),
+        }
     }
 
-    pub fn get_files_matching_regex(&self, regex: &str) -> Vec<PathBuf> {
-        self.index.regex_search(regex)
+    /// Same as find_loaded, but return None if the file is not indexed and only in the decompression cache
+    fn find_accessible(&self, path: &str) -> Result<Option<AssetHandle>> {
+        match self.find_loaded(path) {
+            Err(_) if self.cache.get(path).is_some() => Ok(None),
+            result => result,
+        }
+    }
+
+    /// Return the handle of a loaded file, using the path given or the indexed one
+    fn find_loaded(&self, path: &str) -> Result<Option<AssetHandle>> {
+        if let Some(handle) = self.find_file_index_using_full_path(path) {
+            return Ok(Some(handle));
+        }
+
+        let path = match self.indexed_path(path) {
+            Ok(path) => path,
+            // A file loaded from an archive using only the path of its entry,
+            // or from a compressed variant, like "level.bin" from "level.bin.zst"
+            Err(Error::NotIndexed(_)) => match self.find_loaded_entry(path)? {
+                Some(handle) => return Ok(Some(handle)),
+                None => match self.find_compressed(path)? {
+                    Some((compressed, codec)) => compressed
+                        .strip_suffix(&format!(".{}", codec.extension()))
+                        .unwrap_or(&compressed)
+                        .to_string(),
+                    None => return Err(Error::NotIndexed(path.to_string())),
+                },
+            },
+            Err(err) => return Err(err),
+        };
+        if path.contains('\\') || path.contains('/') {
+            Ok(self.find_file_index_using_full_path(&path))
+        } else {
+            Ok(self.find_file_index(&path))
+        }
+    }
+
+    /// Return the handle of the file loaded from an archive using the path of its entry,
+    /// or an error if entries with this path are loaded from several archives
+    fn find_loaded_entry(&self, entry: &str) -> Result<Option<AssetHandle>> {
+        let found: Vec<(AssetHandle, PathBuf)> = self
+            .files
+            .iter()
+            .filter(|(_, file)| file.from_archive && file.path.to_string_lossy() == entry)
+            .map(|(handle, file)| (handle, file.full_path()))
+            .collect();
+        match found.len() {
+            0 | 1 => Ok(found.first().map(|(handle, _)| *handle)),
+            _ => Err(Error::Ambiguous(
+                found.into_iter().map(|(_, path)| path).collect(),
+            )),
+        }
+    }
+
+    /// Return the indexed path of a file, or an error if the file is not indexed or ambiguous
+    fn indexed_path(&self, filename: &str) -> Result<String> {
+        self.index.get_path(filename)
     }
 }
@@ -0,0 +1,180 @@
+use crate::backend::{Backend, Metadata, ReadSeek};
+use crate::error::{Error, Result};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+/// A layer of content, like the base game, a DLC or a mod
+#[derive(Debug, Clone)]
+pub struct Mount {
+    pub name: String,
+    pub priority: i32,
+    pub backend: Arc<dyn Backend>,
+}
+
+/// Stack of mounts, each file is read from the layer with the highest priority containing it.
+/// With the same priority, the last mounted layer wins.
+#[derive(Debug, Default)]
+pub struct OverlayBackend {
+    mounts: RwLock<Vec<Mount>>,
+}
+
+impl OverlayBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a layer, replacing the layer with the same name
+    pub fn mount(&self, name: &str, backend: Arc<dyn Backend>, priority: i32) {
+        let mut mounts = self.mounts.write().unwrap();
+        mounts.retain(|mount| mount.name != name);
+        let position = mounts
+            .iter()
+            .position(|mount| mount.priority <= priority)
+            .unwrap_or(mounts.len());
+        mounts.insert(
+            position,
+            Mount {
+                name: name.to_string(),
+                priority,
+                backend,
+            },
+        );
+    }
+
+    /// Remove a layer, and return it if it was mounted
+    pub fn unmount(&self, name: &str) -> Option<Mount> {
+        let mut mounts = self.mounts.write().unwrap();
+        let position = mounts.iter().position(|mount| mount.name == name)?;
+        Some(mounts.remove(position))
+    }
+
+    /// Return the layers, from the highest priority to the lowest
+    pub fn mounts(&self) -> Vec<Mount> {
+        self.mounts.read().unwrap().clone()
+    }
+
+    /// Return the name of the layer supplying the file
+    pub fn layer_of(&self, path: &Path) -> Option<String> {
+        self.find(path).map(|mount| mount.name)
+    }
+
+    fn find(&self, path: &Path) -> Option<Mount> {
+        let mounts = self.mounts.read().unwrap();
+        mounts
+            .iter()
+            .find(|mount| match mount.backend.stat(path) {
+                Ok(metadata) => !metadata.is_dir,
+                Err(_) => false,
+            })
+            .cloned()
+    }
+
+    /// Write using the layer with the highest priority accepting it
+    fn write_with(&self, path: &Path, write: impl Fn(&dyn Backend) -> Result<()>) -> Result<()> {
+        let mut last_error = Self::not_found(path);
+        for mount in self.mounts() {
+            match write(mount.backend.as_ref()) {
+                Ok(_) => return Ok(()),
+                Err(err) => last_error = err,
+            }
+        }
+        Err(last_error)
+    }
+
+    fn not_found(path: &Path) -> Error {
+        Error::Io(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("File \"{}\" not found in the mounts", path.display()),
+        ))
+    }
+}
+
+impl Backend for OverlayBackend {
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        match self.find(path) {
+            Some(mount) => mount.backend.read(path),
+            None => Err(Self::not_found(path)),
+        }
+    }
+
+    fn open(&self, path: &Path) -> Result<Box<dyn ReadSeek>> {
+        match self.find(path) {
+            Some(mount) => mount.backend.open(path),
+            None => Err(Self::not_found(path)),
+        }
+    }
+
+    /// Write to the layer with the highest priority accepting it, so the lower layers are never modified
+    fn write(&self, path: &Path, data: &[u8]) -> Result<()> {
+        self.write_with(path, |backend| backend.write(path, data))
+    }
+
+    fn write_atomic(&self, path: &Path, data: &[u8]) -> Result<()> {
+        self.write_with(path, |backend| backend.write_atomic(path, data))
+    }
+
+    fn list(&self, path: &Path) -> Result<Vec<PathBuf>> {
+        let mut found = false;
+        let mut result = Vec::<PathBuf>::new();
+        let mut seen = HashSet::<PathBuf>::new();
+        for mount in self.mounts() {
+            if let Ok(files) = mount.backend.list(path) {
+                found = true;
+                for file in files {
+                    if seen.insert(file.clone()) {
+                        result.push(file);
+                    }
+                }
+            }
+        }
+
+        if found {
+            Ok(result)
+        } else {
+            Err(Self::not_found(path))
+        }
+    }
+
+    fn stat(&self, path: &Path) -> Result<Metadata> {
+        let mut directory = None;
+        for mount in self.mounts() {
+            match mount.backend.stat(path) {
+                Ok(metadata) if !metadata.is_dir => return Ok(metadata),
+                Ok(metadata) => directory = directory.or(Some(metadata)),
+                Err(_) => (),
+            }
+        }
+        directory.ok_or_else(|| Self::not_found(path))
+    }
+
+    /// Remove the file from the writable layer with the highest priority, the file of a lower layer become visible.
+    /// Like the writes, the lower layers are never modified
+    fn remove(&self, path: &Path) -> Result<()> {
+        let mount = match self
+            .mounts()
+            .into_iter()
+            .find(|mount| !mount.backend.is_read_only())
+        {
+            Some(mount) => mount,
+            None => return Err(Self::not_found(path)),
+        };
+        if mount.backend.exists(path) {
+            mount.backend.remove(path)
+        } else if self.find(path).is_some() {
+            Err(Error::Unsupported(format!(
+                "Cannot remove \"{}\", supplied by a lower layer than \"{}\"",
+                path.display(),
+                mount.name
+            )))
+        } else {
+            Err(Self::not_found(path))
+        }
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.mounts()
+            .iter()
+            .all(|mount| mount.backend.is_read_only())
+    }
+}
@@ -0,0 +1,127 @@
+use crate::backend::Backend;
+use crate::error::Result;
+use crate::index::Index;
+use crate::index_file::{hash_content, IndexEntry, IndexFile};
+use std::collections::HashMap;
+use std::io::{Cursor, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use zip::CompressionMethod;
+
+/// Extensions of the files already compressed, stored without compression by default
+const STORED_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "webp", "ogg", "mp3", "zip", "gz", "tgz", "zst", "tzst", "lz4",
+];
+
+/// Build a zip bundle from indexed files, with a matching index file.
+/// The entries are named relatively to the root of the index,
+/// and indexed as "bundle.zip/entry" so AssetsManager::load can read them.
+#[derive(Debug, Clone)]
+pub struct Packer {
+    root: PathBuf,
+    files: Vec<PathBuf>,
+    backend: Arc<dyn Backend>,
+    /// Compression method of the files by extension, in lowercase and without the dot
+    compression: HashMap<String, CompressionMethod>,
+    default_compression: CompressionMethod,
+}
+
+impl Packer {
+    /// Pack all the files of the index, read using its backend
+    pub fn from_index(index: &Index) -> Self {
+        Self::with_files(index, index.files().to_vec())
+    }
+
+    /// Pack the files of the index whose full path match the regex
+    pub fn from_regex(index: &Index, filter: &str) -> Result<Self> {
+        Ok(Self::with_files(index, index.regex_search(filter)?))
+    }
+
+    fn with_files(index: &Index, files: Vec<PathBuf>) -> Self {
+        Self {
+            root: index.root.clone(),
+            files,
+            backend: index.backend(),
+            compression: STORED_EXTENSIONS
+                .iter()
+                .map(|extension| (extension.to_string(), CompressionMethod::Stored))
+                .collect(),
+            default_compression: CompressionMethod::Deflated,
+        }
+    }
+
+    /// Set the compression method of the files with the extension, like "png" or "txt"
+    pub fn set_compression(&mut self, extension: &str, method: CompressionMethod) {
+        self.compression
+            .insert(extension.trim_start_matches('.').to_lowercase(), method);
+    }
+
+    /// Set the compression method of the extensions without their own method, Deflated by default
+    pub fn set_default_compression(&mut self, method: CompressionMethod) {
+        self.default_compression = method;
+    }
+
+    pub fn compression_of(&self, path: &Path) -> CompressionMethod {
+        path.extension()
+            .and_then(|extension| {
+                self.compression
+                    .get(&extension.to_string_lossy().to_lowercase())
+            })
+            .copied()
+            .unwrap_or(self.default_compression)
+    }
+
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Return the name of the file in the bundle
+    pub fn entry_name(&self, file: &Path) -> String {
+        let relative = file.strip_prefix(&self.root).unwrap_or(file);
+        let components: Vec<String> = relative
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(name) => Some(name.to_string_lossy().to_string()),
+                _ => None,
+            })
+            .collect();
+        components.join("/")
+    }
+
+    /// Write the bundle using the backend of the index, replacing it atomically,
+    /// and the index file on the disk. The root of the index file is the directory of the bundle.
+    pub fn pack(&self, bundle: &str, index_file: &str) -> Result<IndexFile> {
+        let bundle_path = PathBuf::from(bundle);
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::<u8>::new()));
+        let mut entries = Vec::<IndexEntry>::new();
+        for file in self.files.iter() {
+            let data = self.backend.read(file)?;
+            let name = self.entry_name(file);
+            let options = zip::write::FileOptions::default()
+                .compression_method(self.compression_of(file))
+                .large_file(data.len() as u64 >= u32::MAX as u64);
+            writer.start_file(name.as_str(), options)?;
+            writer.write_all(&data)?;
+
+            entries.push(IndexEntry {
+                path: bundle_path.join(&name),
+                len: data.len() as u64,
+                modified: self.backend.stat(file)?.modified,
+                hash: hash_content(&data),
+            });
+        }
+        self.backend
+            .write_atomic(&bundle_path, &writer.finish()?.into_inner())?;
+
+        let index = IndexFile {
+            root: bundle_path
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default(),
+            entries,
+            directories: Vec::new(),
+        };
+        std::fs::write(index_file, index.to_bytes())?;
+        Ok(index)
+    }
+}
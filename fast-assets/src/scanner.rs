@@ -0,0 +1,75 @@
+use crate::error::Result;
+use std::fmt::Debug;
+
+/// Find the strings in the content of the files matching some extensions,
+/// the strings matching indexed files are discovered dependencies
+pub trait DependencyScanner: Debug {
+    /// Return the extensions supported by the scanner (without the dot)
+    fn extensions(&self) -> Vec<String>;
+
+    /// Return the strings of the content that can reference a file
+    fn scan(&self, data: &[u8]) -> Result<Vec<String>>;
+}
+
+/// Return the string values of JSON files, at any depth
+#[derive(Debug, Default)]
+pub struct JsonScanner {}
+
+impl DependencyScanner for JsonScanner {
+    fn extensions(&self) -> Vec<String> {
+        vec![String::from("json")]
+    }
+
+    fn scan(&self, data: &[u8]) -> Result<Vec<String>> {
+        let content = json::parse(std::str::from_utf8(data)?)?;
+        let mut result = Vec::<String>::new();
+        collect_strings(&content, &mut result);
+        Ok(result)
+    }
+}
+
+fn collect_strings(value: &json::JsonValue, result: &mut Vec<String>) {
+    if let Some(string) = value.as_str() {
+        result.push(string.to_string());
+    } else if value.is_array() {
+        value
+            .members()
+            .for_each(|member| collect_strings(member, result));
+    } else {
+        value
+            .entries()
+            .for_each(|(_, value)| collect_strings(value, result));
+    }
+}
+
+/// Return the cells of CSV files
+#[derive(Debug)]
+pub struct CsvScanner {
+    pub separator: char,
+}
+
+impl Default for CsvScanner {
+    fn default() -> Self {
+        Self { separator: ';' }
+    }
+}
+
+impl DependencyScanner for CsvScanner {
+    fn extensions(&self) -> Vec<String> {
+        vec![String::from("csv")]
+    }
+
+    fn scan(&self, data: &[u8]) -> Result<Vec<String>> {
+        let content = std::str::from_utf8(data)?;
+        let mut csv = pro_csv::CSV::default();
+        csv.set_sperator_char(self.separator);
+        csv.load(content);
+        Ok(csv
+            .get_all()
+            .into_iter()
+            .flatten()
+            .map(|cell| cell.trim().to_string())
+            .filter(|cell| !cell.is_empty())
+            .collect())
+    }
+}
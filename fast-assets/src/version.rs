@@ -0,0 +1,155 @@
+use crate::error::{Error, Result};
+use std::fmt::{Display, Formatter};
+
+/// Version of an asset or an archive, like "1.4.2"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl Version {
+    pub fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Parse "1.4.2", the missing parts are 0 like "1.4"
+    pub fn parse(text: &str) -> Result<Self> {
+        let (version, _) = parse_parts(text)?;
+        Ok(version)
+    }
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Parse a version, and return the number of parts written
+fn parse_parts(text: &str) -> Result<(Version, usize)> {
+    let invalid = || Error::Parse(format!("Invalid version \"{}\"", text));
+    let text = text.trim();
+    let text = text.strip_prefix('v').unwrap_or(text);
+    let parts = text
+        .split('.')
+        .map(|part| part.parse::<u64>().map_err(|_| invalid()))
+        .collect::<Result<Vec<u64>>>()?;
+    if parts.is_empty() || parts.len() > 3 {
+        return Err(invalid());
+    }
+
+    let part = |i: usize| parts.get(i).copied().unwrap_or(0);
+    Ok((Version::new(part(0), part(1), part(2)), parts.len()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Exact,
+    Greater,
+    GreaterEq,
+    Less,
+    LessEq,
+    /// Same major version, or same minor version if the major is 0
+    Caret,
+    /// Same minor version, or same major version if only the major is written
+    Tilde,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Comparator {
+    op: Op,
+    version: Version,
+    parts: usize,
+}
+
+impl Comparator {
+    fn matches(&self, version: &Version) -> bool {
+        let v = &self.version;
+        match self.op {
+            Op::Exact => match self.parts {
+                1 => version.major == v.major,
+                2 => (version.major, version.minor) == (v.major, v.minor),
+                _ => version == v,
+            },
+            Op::Greater => version > v,
+            Op::GreaterEq => version >= v,
+            Op::Less => version < v,
+            Op::LessEq => version <= v,
+            Op::Caret => {
+                version >= v
+                    && match (v.major, v.minor) {
+                        (0, 0) if self.parts > 2 => version == v,
+                        (0, minor) if self.parts > 1 => {
+                            version.major == 0 && version.minor == minor
+                        }
+                        (major, _) => version.major == major,
+                    }
+            }
+            Op::Tilde => {
+                version >= v
+                    && match self.parts {
+                        1 => version.major == v.major,
+                        _ => (version.major, version.minor) == (v.major, v.minor),
+                    }
+            }
+        }
+    }
+}
+
+/// A semver-style version requirement, like "^1.2", "~1.4.0", ">=1.0, <2.0" or "*".
+/// A version without operator is a caret requirement, like in Cargo.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionReq {
+    text: String,
+    comparators: Vec<Comparator>,
+}
+
+impl VersionReq {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut comparators = Vec::<Comparator>::new();
+        for comparator in text.split(',').map(str::trim) {
+            if comparator == "*" {
+                continue;
+            }
+
+            let (op, version) = [
+                (">=", Op::GreaterEq),
+                ("<=", Op::LessEq),
+                (">", Op::Greater),
+                ("<", Op::Less),
+                ("=", Op::Exact),
+                ("^", Op::Caret),
+                ("~", Op::Tilde),
+            ]
+            .iter()
+            .find_map(|(prefix, op)| comparator.strip_prefix(prefix).map(|rest| (*op, rest)))
+            .unwrap_or((Op::Caret, comparator));
+            let (version, parts) = parse_parts(version)?;
+            comparators.push(Comparator { op, version, parts });
+        }
+
+        Ok(Self {
+            text: text.trim().to_string(),
+            comparators,
+        })
+    }
+
+    /// Return true if the version respects all the comparators
+    pub fn matches(&self, version: &Version) -> bool {
+        self.comparators
+            .iter()
+            .all(|comparator| comparator.matches(version))
+    }
+}
+
+impl Display for VersionReq {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.text)
+    }
+}
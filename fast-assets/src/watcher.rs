@@ -0,0 +1,173 @@
+use crate::error::Result;
+use crate::index::Index;
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Modified,
+    Removed,
+}
+
+/// A file changed in the backend since the last check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetChange {
+    pub path: PathBuf,
+    pub kind: ChangeKind,
+}
+
+/// Modification time and size of a file
+type FileState = (Option<SystemTime>, u64);
+
+/// Detect the changes in the root of an index by polling the modification time of the files.
+/// Only the directories modified since the last scan are listed again,
+/// and the archives are only listed when added or modified.
+#[derive(Debug, Default)]
+pub struct Watcher {
+    files: HashMap<PathBuf, FileState>,
+    /// Modification time of the directories, with their files and sub directories
+    directories: HashMap<PathBuf, (Option<SystemTime>, Vec<PathBuf>)>,
+    /// State of the archives and their entries, if the index contains the entries of the archives
+    archives: HashMap<PathBuf, (FileState, Vec<PathBuf>)>,
+}
+
+impl Watcher {
+    /// Create a watcher, the files already present are not reported as added
+    pub fn new(index: &Index) -> Result<Self> {
+        let mut watcher = Self::default();
+        watcher.scan(index)?;
+        Ok(watcher)
+    }
+
+    /// Return the files added, modified and removed since the last scan,
+    /// including the entries of the archives added or removed if the index contains them
+    pub fn scan(&mut self, index: &Index) -> Result<Vec<AssetChange>> {
+        let mut changes = Vec::<AssetChange>::new();
+        let mut next = Self::default();
+        if index.backend().exists(&index.root) {
+            self.scan_dir(index, index.root.to_path_buf(), &mut next, &mut changes)?;
+        }
+
+        let removed = self
+            .files
+            .keys()
+            .filter(|path| !next.files.contains_key(*path));
+        let removed_entries = self
+            .archives
+            .iter()
+            .filter(|(archive, _)| !next.archives.contains_key(*archive))
+            .flat_map(|(_, (_, entries))| entries.iter());
+        for path in removed.chain(removed_entries) {
+            changes.push(AssetChange {
+                path: path.clone(),
+                kind: ChangeKind::Removed,
+            });
+        }
+
+        *self = next;
+        changes.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(changes)
+    }
+
+    fn scan_dir(
+        &self,
+        index: &Index,
+        directory: PathBuf,
+        next: &mut Self,
+        changes: &mut Vec<AssetChange>,
+    ) -> Result<()> {
+        let backend = index.backend();
+        let modified = match backend.stat(&directory) {
+            Ok(metadata) => metadata.modified,
+            // Removed since listed, it will be reported on the next scan
+            Err(_) => return Ok(()),
+        };
+        let items = match self.directories.get(&directory) {
+            // Unchanged, only the known items are checked
+            Some((known, items)) if modified.is_some() && *known == modified => items.clone(),
+            _ => backend.list(&directory)?,
+        };
+
+        let mut watched = Vec::<PathBuf>::new();
+        for path in items {
+            let metadata = match backend.stat(&path) {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            };
+            if metadata.is_dir {
+                self.scan_dir(index, path.clone(), next, changes)?;
+                watched.push(path);
+                continue;
+            }
+
+            let state = (metadata.modified, metadata.len);
+            let matching = match path.file_name() {
+                Some(name) => index.filter.is_match(&name.to_string_lossy()),
+                None => false,
+            };
+            let archive = index.is_archive_indexing_enabled() && index.is_archive(&path);
+            if !matching && !archive {
+                continue;
+            }
+            watched.push(path.clone());
+
+            if matching {
+                match self.files.get(&path) {
+                    None => changes.push(AssetChange {
+                        path: path.clone(),
+                        kind: ChangeKind::Added,
+                    }),
+                    Some(previous) if *previous != state => changes.push(AssetChange {
+                        path: path.clone(),
+                        kind: ChangeKind::Modified,
+                    }),
+                    _ => (),
+                }
+                next.files.insert(path.clone(), state);
+            }
+
+            if archive {
+                let entries = match self.archives.get(&path) {
+                    Some((previous, entries)) if *previous == state => entries.clone(),
+                    previous => {
+                        let entries = index.search_in_archive(&path)?;
+                        let known = previous.map(|(_, entries)| entries.as_slice());
+                        diff_entries(known.unwrap_or_default(), &entries, changes);
+                        entries
+                    }
+                };
+                next.archives.insert(path, (state, entries));
+            }
+        }
+
+        next.directories.insert(directory, (modified, watched));
+        Ok(())
+    }
+}
+
+/// Report the entries added and removed from an archive
+fn diff_entries(previous: &[PathBuf], entries: &[PathBuf], changes: &mut Vec<AssetChange>) {
+    let previous_set: HashSet<&PathBuf> = previous.iter().collect();
+    let entries_set: HashSet<&PathBuf> = entries.iter().collect();
+    for entry in entries.iter().filter(|entry| !previous_set.contains(entry)) {
+        changes.push(AssetChange {
+            path: entry.clone(),
+            kind: ChangeKind::Added,
+        });
+    }
+    for entry in previous.iter().filter(|entry| !entries_set.contains(entry)) {
+        changes.push(AssetChange {
+            path: entry.clone(),
+            kind: ChangeKind::Removed,
+        });
+    }
+}
+
+/// Return true if both paths target the same file, ignoring the "./" components
+pub(crate) fn same_path(a: &Path, b: &Path) -> bool {
+    let a = a.components().filter(|c| *c != Component::CurDir);
+    let b = b.components().filter(|c| *c != Component::CurDir);
+    a.eq(b)
+}
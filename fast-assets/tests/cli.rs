@@ -0,0 +1,201 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// A directory in the temporary directory, removed when dropped
+struct TestDir(PathBuf);
+
+impl TestDir {
+    fn new(name: &str) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("fast-assets-cli-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    fn join(&self, path: &str) -> PathBuf {
+        self.0.join(path)
+    }
+
+    fn write(&self, path: &str, content: &str) {
+        let path = self.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+fn run(args: &[&Path]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_fast-assets"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+#[test]
+fn help() {
+    let output = run(&[Path::new("--help")]);
+    assert!(output.status.success());
+    assert!(stdout(&output).starts_with("Usage: fast-assets"));
+}
+
+#[test]
+fn index_build_and_verify() {
+    let dir = TestDir::new("verify");
+    dir.write("assets/level1.json", "{}");
+    dir.write("assets/textures/grass.png", "grass");
+    // The dependencies and versions are beside the index, outside of the indexed directory
+    dir.write(
+        "deps.json",
+        r#"{"dependencies": {"level1.json": ["grass.png", {"pattern": "textures/*.png", "version": "^1.0"}]}}"#,
+    );
+    dir.write("versions.json", r#"{"versions": {"grass.png": "1.2.0"}}"#);
+    let index = dir.join("index.bin");
+
+    let output = run(&[
+        Path::new("index"),
+        Path::new("build"),
+        &dir.join("assets"),
+        Path::new("--out"),
+        &index,
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).starts_with("2 files indexed"));
+
+    let output = run(&[
+        Path::new("verify"),
+        Path::new("--index"),
+        &index,
+        Path::new("--deps"),
+        &dir.join("deps.json"),
+        Path::new("--versions"),
+        &dir.join("versions.json"),
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(stdout(&output), "ok\n");
+
+    let output = run(&[
+        Path::new("deps"),
+        Path::new("graph"),
+        &dir.join("deps.json"),
+        Path::new("--index"),
+        &index,
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).starts_with("level1.json\n  -> grass.png\n"));
+
+    // A file removed after the indexing, and required by the dependencies
+    std::fs::remove_file(dir.join("assets/textures/grass.png")).unwrap();
+    let output = run(&[
+        Path::new("verify"),
+        Path::new("--index"),
+        &index,
+        Path::new("--deps"),
+        &dir.join("deps.json"),
+    ]);
+    assert!(!output.status.success());
+    assert!(stdout(&output).contains("missing file: "));
+    assert!(stdout(&output).contains("missing dependency: level1.json requires grass.png"));
+}
+
+#[test]
+fn index_ls_and_find() {
+    let dir = TestDir::new("ls");
+    dir.write("assets/level1.json", "{}");
+    dir.write("assets/textures/grass.png", "grass");
+    let index = dir.join("index.bin");
+    let output = run(&[
+        Path::new("index"),
+        Path::new("build"),
+        &dir.join("assets"),
+        Path::new("--out"),
+        &index,
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+
+    let output = run(&[
+        Path::new("index"),
+        Path::new("ls"),
+        Path::new("--index"),
+        &index,
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    let mut lines: Vec<String> = stdout(&output).lines().map(String::from).collect();
+    lines.sort();
+    assert_eq!(
+        lines,
+        vec![
+            format!("{}\t2", dir.join("assets/level1.json").display()),
+            format!("{}\t5", dir.join("assets/textures/grass.png").display()),
+        ]
+    );
+
+    let output = run(&[
+        Path::new("index"),
+        Path::new("find"),
+        Path::new("grass.png"),
+        Path::new("--index"),
+        &index,
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        stdout(&output),
+        format!("{}\n", dir.join("assets/textures/grass.png").display())
+    );
+
+    let output = run(&[
+        Path::new("index"),
+        Path::new("find"),
+        Path::new("stone.png"),
+        Path::new("--index"),
+        &index,
+    ]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn pack_and_unpack() {
+    let dir = TestDir::new("pack");
+    let files = [
+        ("assets/level1.json", "{}"),
+        ("assets/textures/grass.png", "grass"),
+        ("assets/textures/trees/oak.png", "oak"),
+    ];
+    for (path, content) in files {
+        dir.write(path, content);
+    }
+    let bundle = dir.join("out/bundle.zip");
+
+    let output = run(&[
+        Path::new("pack"),
+        &dir.join("assets"),
+        Path::new("--out"),
+        &bundle,
+        Path::new("--index-out"),
+        &dir.join("out/bundle.bin"),
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).starts_with("3 files packed"));
+
+    let output = run(&[
+        Path::new("unpack"),
+        &bundle,
+        Path::new("--out"),
+        &dir.join("unpacked"),
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout(&output).starts_with("3 files extracted"));
+    for (path, content) in files {
+        let unpacked = dir.join(&path.replacen("assets", "unpacked", 1));
+        assert_eq!(std::fs::read_to_string(unpacked).unwrap(), content);
+    }
+}